@@ -0,0 +1,519 @@
+//! Small typed wrappers around e621 API endpoints that e-cli doesn't expose.
+//! Everything here is blocking and meant to be called from a background thread.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use e_cli::commands::get_client;
+use e_cli::type_defs::api_defs::Post;
+use e_cli::Login;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::tagdb::{Suggestion, TagEntry};
+
+#[derive(Clone, Deserialize)]
+pub struct PoolSummary {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub creator_name: String,
+    #[serde(default)]
+    pub post_count: u64,
+}
+
+impl PoolSummary {
+    pub fn display_name(&self) -> String {
+        self.name.replace('_', " ")
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PostSet {
+    pub name: String,
+    #[serde(default)]
+    pub shortname: String,
+    #[serde(default)]
+    pub post_ids: Vec<u64>,
+}
+
+/// An artist's entry on the site: the names and links the artist is known by.
+#[derive(Clone, Deserialize)]
+pub struct ArtistEntry {
+    #[serde(default)]
+    pub id: u64,
+    #[serde(default)]
+    pub other_names: Vec<String>,
+    #[serde(default)]
+    pub urls: Vec<ArtistUrl>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ArtistUrl {
+    pub url: String,
+    #[serde(default)]
+    pub is_active: bool,
+}
+
+#[derive(Deserialize)]
+struct AutocompleteTag {
+    #[serde(flatten)]
+    tag: TagEntry,
+    #[serde(default)]
+    antecedent_name: Option<String>,
+}
+
+/// Aliases and implications, as (antecedent, consequent) pairs.
+pub type TagRelations = (Vec<(String, String)>, Vec<(String, String)>);
+
+#[derive(Deserialize)]
+struct TagRelation {
+    antecedent_name: String,
+    consequent_name: String,
+}
+
+#[derive(Deserialize)]
+struct PostIdOnly {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct PostIdPage {
+    posts: Vec<PostIdOnly>,
+}
+
+#[derive(Deserialize)]
+struct PostPage {
+    posts: Vec<Post>,
+}
+
+/// Where a post search pages from, by post id. Results are newest (highest id) first;
+/// `Before` walks towards older posts and `After` towards newer ones.
+#[derive(Clone, Copy)]
+pub enum Cursor {
+    Before(u64),
+    After(u64),
+}
+
+/// Post references pasted by the user, before md5s are resolved to ids.
+#[derive(Default)]
+pub struct PostRefs {
+    pub ids: Vec<u64>,
+    pub md5s: Vec<String>,
+    pub unrecognised: usize,
+}
+
+/// Pause between consecutive requests of one job; e621 allows about two per second.
+pub const REQUEST_DELAY: Duration = Duration::from_millis(500);
+
+/// Spaces out a job's requests by [`REQUEST_DELAY`]; call [`Throttle::wait`] before each.
+#[derive(Default)]
+pub struct Throttle {
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn wait(&mut self) {
+        if let Some(last) = self.last {
+            thread::sleep(REQUEST_DELAY.saturating_sub(last.elapsed()));
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+pub fn base_url(nsfw: bool) -> &'static str {
+    if nsfw {
+        "https://e621.net"
+    } else {
+        "https://e926.net"
+    }
+}
+
+fn get_json<T: DeserializeOwned>(
+    nsfw: bool,
+    login: &Login,
+    endpoint: &str,
+    query: &[(&str, String)],
+) -> Result<T, String> {
+    let mut request = get_client()
+        .get(format!("{}/{endpoint}", base_url(nsfw)))
+        .query(query);
+    if !login.api_key.is_empty() {
+        request = request.basic_auth(&login.username, Some(&login.api_key));
+    }
+    let response = request
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|error| format!("Request to {endpoint} failed: {error}"))?;
+    response
+        .json()
+        .map_err(|error| format!("Unexpected response from {endpoint}: {error}"))
+}
+
+/// Like [`get_json`] for list endpoints, which answer an empty search with an object
+/// such as `{"tag_aliases":[]}` instead of `[]`.
+fn get_list<T: DeserializeOwned>(
+    nsfw: bool,
+    login: &Login,
+    endpoint: &str,
+    query: &[(&str, String)],
+) -> Result<Vec<T>, String> {
+    match get_json::<serde_json::Value>(nsfw, login, endpoint, query)? {
+        value @ serde_json::Value::Array(_) => serde_json::from_value(value)
+            .map_err(|error| format!("Unexpected response from {endpoint}: {error}")),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Searches pools by (partial) name and/or creator, most recently updated first.
+pub fn search_pools(
+    nsfw: bool,
+    login: &Login,
+    name: &str,
+    creator: &str,
+) -> Result<Vec<PoolSummary>, String> {
+    let mut query = vec![("limit", "75".to_owned())];
+    if !name.trim().is_empty() {
+        query.push((
+            "search[name_matches]",
+            format!("*{}*", name.trim().replace(' ', "_")),
+        ));
+    }
+    if !creator.trim().is_empty() {
+        query.push(("search[creator_name]", creator.trim().to_owned()));
+    }
+    get_json(nsfw, login, "pools.json", &query)
+}
+
+/// Fetches a set by numeric id, `/post_sets/<id>` URL or shortname.
+pub fn get_set(nsfw: bool, login: &Login, set: &str) -> Result<PostSet, String> {
+    let set = set.trim();
+    let id = set.parse().ok().or_else(|| id_from_url(set, "/post_sets/"));
+    if let Some(id) = id {
+        return get_json(nsfw, login, &format!("post_sets/{id}.json"), &[]);
+    }
+    let sets: Vec<PostSet> = get_json(
+        nsfw,
+        login,
+        "post_sets.json",
+        &[("search[shortname]", set.to_owned())],
+    )?;
+    sets.into_iter()
+        .find(|found| found.shortname.eq_ignore_ascii_case(set))
+        .ok_or_else(|| format!("Set '{set}' not found."))
+}
+
+/// The site's own tag suggestions for a partial name, aliases included.
+pub fn autocomplete_tags(
+    nsfw: bool,
+    login: &Login,
+    prefix: &str,
+) -> Result<Vec<Suggestion>, String> {
+    let tags: Vec<AutocompleteTag> = get_list(
+        nsfw,
+        login,
+        "tags/autocomplete.json",
+        &[
+            ("search[name_matches]", prefix.to_owned()),
+            ("expiry", "7".to_owned()),
+        ],
+    )?;
+    Ok(tags
+        .into_iter()
+        .map(|found| Suggestion {
+            tag: found.tag,
+            alias: found.antecedent_name,
+        })
+        .collect())
+}
+
+/// The artist entry for an artist tag, if the site has one.
+pub fn artist_entry(nsfw: bool, login: &Login, name: &str) -> Result<Option<ArtistEntry>, String> {
+    let found: Vec<ArtistEntry> = get_list(
+        nsfw,
+        login,
+        "artists.json",
+        &[("search[name]", name.to_owned()), ("limit", "1".to_owned())],
+    )?;
+    let Some(artist) = found.into_iter().next() else {
+        return Ok(None);
+    };
+    if !artist.urls.is_empty() {
+        return Ok(Some(artist));
+    }
+    // Search results may leave the URLs out; the entry itself has them.
+    get_json(nsfw, login, &format!("artists/{}.json", artist.id), &[]).map(Some)
+}
+
+/// How many posts carry `tag`; 0 if the tag doesn't exist.
+pub fn tag_post_count(nsfw: bool, login: &Login, tag: &str) -> Result<u64, String> {
+    let tags: Vec<TagEntry> = get_list(
+        nsfw,
+        login,
+        "tags.json",
+        &[("search[name]", tag.to_owned())],
+    )?;
+    Ok(tags.first().map_or(0, |found| found.post_count))
+}
+
+/// Active aliases and implications whose antecedent is one of `tags`, as
+/// (antecedent, consequent) pairs. Implied tags are followed a few levels deep.
+pub fn tag_relations(nsfw: bool, login: &Login, tags: &[String]) -> Result<TagRelations, String> {
+    let fetch = |endpoint: &str, names: &[String]| -> Result<Vec<(String, String)>, String> {
+        let mut pairs = Vec::new();
+        // Keep URLs short; the site accepts comma-separated names.
+        for chunk in names.chunks(40) {
+            let found: Vec<TagRelation> = get_list(
+                nsfw,
+                login,
+                endpoint,
+                &[
+                    ("search[antecedent_name]", chunk.join(",")),
+                    ("search[status]", "active".to_owned()),
+                    ("limit", "320".to_owned()),
+                ],
+            )?;
+            pairs.extend(
+                found
+                    .into_iter()
+                    .map(|relation| (relation.antecedent_name, relation.consequent_name)),
+            );
+        }
+        Ok(pairs)
+    };
+    let aliases = fetch("tag_aliases.json", tags)?;
+    let mut implications = Vec::new();
+    let mut names: Vec<String> = tags
+        .iter()
+        .map(|tag| {
+            aliases
+                .iter()
+                .find(|(antecedent, _)| antecedent == tag)
+                .map_or(tag, |(_, consequent)| consequent)
+                .clone()
+        })
+        .collect();
+    for _ in 0..3 {
+        if names.is_empty() {
+            break;
+        }
+        let found = fetch("tag_implications.json", &names)?;
+        names = found
+            .iter()
+            .map(|(_, consequent)| consequent.clone())
+            .filter(|consequent| {
+                !implications
+                    .iter()
+                    .any(|(antecedent, _)| antecedent == consequent)
+            })
+            .collect();
+        implications.extend(found);
+    }
+    Ok((aliases, implications))
+}
+
+/// One page of a post search. Without a cursor this is the first page; with one it's the
+/// page next to that post id (`page=b<id>`/`page=a<id>`), which unlike numbered pages
+/// works arbitrarily deep into a result.
+pub fn search_posts(
+    nsfw: bool,
+    login: &Login,
+    tags: &str,
+    limit: u32,
+    cursor: Option<Cursor>,
+) -> Result<Vec<Post>, String> {
+    let mut query = vec![("tags", tags.to_owned()), ("limit", limit.to_string())];
+    match cursor {
+        Some(Cursor::Before(id)) => query.push(("page", format!("b{id}"))),
+        Some(Cursor::After(id)) => query.push(("page", format!("a{id}"))),
+        None => {}
+    }
+    let page: PostPage = get_json(nsfw, login, "posts.json", &query)?;
+    Ok(page.posts)
+}
+
+/// The logged-in user's blacklist, one rule per line; empty without a login.
+pub fn blacklist(nsfw: bool, login: &Login) -> Result<String, String> {
+    #[derive(Deserialize)]
+    struct User {
+        #[serde(default)]
+        blacklisted_tags: Option<String>,
+    }
+
+    if login.username.is_empty() || login.api_key.is_empty() {
+        return Ok(String::new());
+    }
+    let user: User = get_json(nsfw, login, &format!("users/{}.json", login.username), &[])?;
+    Ok(user.blacklisted_tags.unwrap_or_default())
+}
+
+/// The popular posts of the day, week or month containing `date` (`YYYY-MM-DD`), without
+/// those `blacklist` hides. Also returns how many were hidden.
+pub fn popular_posts(
+    nsfw: bool,
+    login: &Login,
+    date: &str,
+    scale: crate::popular::Scale,
+    blacklist: &crate::popular::Blacklist,
+) -> Result<(Vec<Post>, usize), String> {
+    #[derive(Deserialize)]
+    struct PopularPage {
+        posts: Vec<serde_json::Value>,
+    }
+
+    let page: PopularPage = get_json(
+        nsfw,
+        login,
+        "popular.json",
+        &[
+            ("date", date.to_owned()),
+            ("scale", scale.param().to_owned()),
+        ],
+    )?;
+    let mut posts = Vec::new();
+    let mut hidden = 0;
+    for value in page.posts {
+        let tags: Vec<&str> = value
+            .get("tags")
+            .and_then(|tags| tags.as_object())
+            .into_iter()
+            .flat_map(|categories| categories.values())
+            .filter_map(|list| list.as_array())
+            .flatten()
+            .filter_map(|tag| tag.as_str())
+            .collect();
+        let rating = value
+            .get("rating")
+            .and_then(|rating| rating.as_str())
+            .unwrap_or_default();
+        if blacklist.hides(&tags, rating) {
+            hidden += 1;
+            continue;
+        }
+        let post: Post = serde_json::from_value(value)
+            .map_err(|error| format!("Unexpected response from popular.json: {error}"))?;
+        posts.push(post);
+    }
+    Ok((posts, hidden))
+}
+
+/// The posts among `posts` that also match the search `filter`, checked by the site so
+/// ratings, metatags and `-tag` exclusions all work as in a normal search.
+pub fn filter_posts(
+    nsfw: bool,
+    login: &Login,
+    posts: Vec<Post>,
+    filter: &str,
+    throttle: &mut Throttle,
+) -> Result<Vec<Post>, String> {
+    let mut matching = std::collections::HashSet::new();
+    for chunk in posts.chunks(100) {
+        let ids: Vec<String> = chunk.iter().map(|post| post.id.to_string()).collect();
+        throttle.wait();
+        let page: PostIdPage = get_json(
+            nsfw,
+            login,
+            "posts.json",
+            &[
+                ("tags", format!("id:{} {filter}", ids.join(","))),
+                ("limit", "320".to_owned()),
+            ],
+        )?;
+        matching.extend(page.posts.into_iter().map(|post| post.id));
+    }
+    Ok(posts
+        .into_iter()
+        .filter(|post| matching.contains(&post.id))
+        .collect())
+}
+
+/// Looks up the post ids for file md5s, 100 per `md5:` search spaced out by `throttle`.
+/// Md5s without a post are left out of the map.
+pub fn resolve_md5s(
+    nsfw: bool,
+    login: &Login,
+    md5s: &[String],
+    cancel: &AtomicBool,
+    throttle: &mut Throttle,
+) -> Result<HashMap<String, u64>, String> {
+    let mut found = HashMap::new();
+    for chunk in md5s.chunks(100) {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        throttle.wait();
+        let page: PostPage = get_json(
+            nsfw,
+            login,
+            "posts.json",
+            &[
+                ("tags", format!("md5:{}", chunk.join(","))),
+                ("limit", "320".to_owned()),
+            ],
+        )?;
+        found.extend(page.posts.into_iter().map(|post| (post.file.md5, post.id)));
+    }
+    Ok(found)
+}
+
+/// Parses one post reference per line: a bare id, a `/posts/<id>` URL, a file md5 or a
+/// static file URL (whose name is the md5).
+pub fn parse_post_refs(text: &str) -> PostRefs {
+    let mut refs = PostRefs::default();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(id) = line.parse().ok().or_else(|| id_from_url(line, "/posts/")) {
+            if !refs.ids.contains(&id) {
+                refs.ids.push(id);
+            }
+            continue;
+        }
+        let file_name = line
+            .rsplit('/')
+            .next()
+            .unwrap_or(line)
+            .split('.')
+            .next()
+            .unwrap_or_default();
+        if file_name.len() == 32 && file_name.chars().all(|c| c.is_ascii_hexdigit()) {
+            let md5 = file_name.to_ascii_lowercase();
+            if !refs.md5s.contains(&md5) {
+                refs.md5s.push(md5);
+            }
+        } else {
+            refs.unrecognised += 1;
+        }
+    }
+    refs
+}
+
+/// Pulls pool ids out of free-form text: bare ids and `/pools/<id>` URLs, separated by
+/// whitespace or commas.
+pub fn parse_pool_ids(text: &str) -> Vec<u64> {
+    let mut ids = Vec::new();
+    for token in text.split(|c: char| c.is_whitespace() || c == ',') {
+        let id = token
+            .trim()
+            .parse()
+            .ok()
+            .or_else(|| id_from_url(token, "/pools/"));
+        if let Some(id) = id {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    ids
+}
+
+/// Reads the number following `segment` (e.g. `/pools/`) in a URL.
+fn id_from_url(url: &str, segment: &str) -> Option<u64> {
+    let start = url.find(segment)? + segment.len();
+    url[start..]
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>()
+        .parse()
+        .ok()
+}
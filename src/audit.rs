@@ -0,0 +1,252 @@
+//! Library integrity audit: hashes everything under the download folder and compares it
+//! with what the duplicate index, the download manifest and the file names say it should
+//! be, so truncated or corrupted files the tracker considers done can be re-downloaded.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use e_cli::duplicate::DuplicateIndex;
+
+use crate::backend::Progress;
+use crate::library;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// Recorded as downloaded but not on disk.
+    Missing,
+    /// Smaller than the recorded size, or empty.
+    Truncated,
+    /// Present but its md5 differs from the recorded one.
+    Mismatched,
+}
+
+impl IssueKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Truncated => "truncated",
+            Self::Mismatched => "mismatched",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuditIssue {
+    pub path: PathBuf,
+    pub kind: IssueKind,
+    /// The md5 the file should have, when known.
+    pub md5: Option<String>,
+    pub post_id: Option<u64>,
+}
+
+/// What is known about one file before looking at it.
+#[derive(Default)]
+struct Expected {
+    md5: Option<String>,
+    size: Option<u64>,
+    post_id: Option<u64>,
+}
+
+/// Audits `dl_dir` on its own thread. Sends `Total`/`Tick` while hashing, then `Audit`
+/// with the issues found and a `Summary`.
+pub fn spawn_audit(
+    dl_dir: PathBuf,
+    index_path: PathBuf,
+    manifest_path: Option<PathBuf>,
+    threads: usize,
+    cancel: Arc<AtomicBool>,
+    tx: Sender<Progress>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        match audit(
+            &dl_dir,
+            &index_path,
+            manifest_path.as_deref(),
+            threads,
+            &cancel,
+            &tx,
+        ) {
+            Ok(Some(issues)) => {
+                let count = |kind| issues.iter().filter(|issue| issue.kind == kind).count();
+                let summary = format!(
+                    "Audit finished: {} missing, {} truncated, {} mismatched.",
+                    count(IssueKind::Missing),
+                    count(IssueKind::Truncated),
+                    count(IssueKind::Mismatched)
+                );
+                let _ = tx.send(Progress::Audit(issues));
+                let _ = tx.send(Progress::Summary(summary));
+            }
+            Ok(None) => {
+                let _ = tx.send(Progress::Cancelled);
+            }
+            Err(error) => {
+                let _ = tx.send(Progress::Error(error));
+            }
+        }
+    })
+}
+
+fn audit(
+    dl_dir: &Path,
+    index_path: &Path,
+    manifest_path: Option<&Path>,
+    threads: usize,
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+) -> Result<Option<Vec<AuditIssue>>, String> {
+    use rayon::prelude::*;
+
+    let _ = tx.send(Progress::Status("Reading index and manifest...".to_owned()));
+    let mut issues = Vec::new();
+    let mut expected: HashMap<PathBuf, Expected> = HashMap::new();
+
+    let index = DuplicateIndex::load(index_path)?;
+    for (md5, entry) in index.iter() {
+        match library::resolve_entry(entry, index_path) {
+            Some(file) => {
+                expected.entry(library::canonical(&file)).or_default().md5 = Some(md5.to_owned())
+            }
+            None => issues.push(AuditIssue {
+                path: entry.to_path_buf(),
+                kind: IssueKind::Missing,
+                md5: Some(md5.to_owned()),
+                post_id: None,
+            }),
+        }
+    }
+    if let Some(manifest_path) = manifest_path {
+        for record in manifest_records(manifest_path)? {
+            let Some(path) = record.path else { continue };
+            if !path.exists() {
+                issues.push(AuditIssue {
+                    path,
+                    kind: IssueKind::Missing,
+                    md5: record.md5,
+                    post_id: record.post_id,
+                });
+                continue;
+            }
+            let known = expected.entry(library::canonical(&path)).or_default();
+            known.md5 = known.md5.take().or(record.md5);
+            known.size = known.size.or(record.size);
+            known.post_id = known.post_id.or(record.post_id);
+        }
+    }
+
+    let _ = tx.send(Progress::Status("Scanning folders...".to_owned()));
+    let mut files = Vec::new();
+    library::collect_media_files(dl_dir, &mut files);
+    let _ = tx.send(Progress::Total(files.len() as u64));
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .build()
+        .expect("Error building thread pool");
+    let checked: Vec<Option<AuditIssue>> = pool.install(|| {
+        files
+            .into_par_iter()
+            .map(|file| {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let known = expected.get(&library::canonical(&file));
+                let issue = check_file(&file, known);
+                let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+                let _ = tx.send(Progress::Tick(size as f64));
+                issue
+            })
+            .collect()
+    });
+    if cancel.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+    issues.extend(checked.into_iter().flatten());
+    Ok(Some(issues))
+}
+
+fn check_file(file: &Path, known: Option<&Expected>) -> Option<AuditIssue> {
+    // Files named after their md5 (as e621 serves them) carry their own checksum.
+    let from_name = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        .filter(|stem| stem.len() == 32 && stem.chars().all(|c| c.is_ascii_hexdigit()));
+    let expected_md5 = known.and_then(|known| known.md5.clone()).or(from_name);
+    let expected_size = known.and_then(|known| known.size);
+    let post_id = known.and_then(|known| known.post_id);
+    let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+
+    let truncated = size == 0 || expected_size.is_some_and(|expected| size < expected);
+    let kind = if truncated {
+        IssueKind::Truncated
+    } else {
+        let expected = expected_md5.as_deref()?;
+        match library::md5_file(file) {
+            Ok(actual) if actual == expected => return None,
+            _ => IssueKind::Mismatched,
+        }
+    };
+    Some(AuditIssue {
+        path: file.to_path_buf(),
+        kind,
+        md5: expected_md5,
+        post_id,
+    })
+}
+
+pub struct ManifestRecord {
+    pub path: Option<PathBuf>,
+    pub md5: Option<String>,
+    pub size: Option<u64>,
+    pub post_id: Option<u64>,
+}
+
+/// Reads every object carrying a `post_id` out of a download manifest.
+pub fn manifest_records(path: &Path) -> Result<Vec<ManifestRecord>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(format!("Could not read {}: {error}", path.display())),
+    };
+    let value: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|error| format!("Invalid manifest {}: {error}", path.display()))?;
+    let mut records = Vec::new();
+    collect_records(&value, &mut records);
+    Ok(records)
+}
+
+fn collect_records(value: &serde_json::Value, records: &mut Vec<ManifestRecord>) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_records(item, records);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            if let Some(post_id) = fields.get("post_id").and_then(|id| id.as_u64()) {
+                let text = |keys: &[&str]| {
+                    keys.iter()
+                        .find_map(|key| fields.get(*key).and_then(|value| value.as_str()))
+                        .map(str::to_owned)
+                };
+                records.push(ManifestRecord {
+                    path: text(&["path", "file", "file_path", "destination"]).map(PathBuf::from),
+                    md5: text(&["md5"]).map(|md5| md5.to_ascii_lowercase()),
+                    size: ["size", "file_size", "bytes"]
+                        .iter()
+                        .find_map(|key| fields.get(*key).and_then(|value| value.as_u64())),
+                    post_id: Some(post_id),
+                });
+                return;
+            }
+            for field in fields.values() {
+                collect_records(field, records);
+            }
+        }
+        _ => {}
+    }
+}
@@ -2,10 +2,11 @@
 //! progress back to the egui update loop over `std::sync::mpsc` channels.
 
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use e_cli::cli::ArchiveFormat;
 use e_cli::commands::get_client;
@@ -22,7 +23,8 @@ pub struct DownloadSettings {
     pub count: u32,
     pub pages: i64,
     pub threads: usize,
-    pub random: bool,
+    /// `order:` value added to tag/favourite searches; `None` is the site's default.
+    pub order: Option<String>,
     pub lower_quality: bool,
     pub track_file: String,
     pub retries: u32,
@@ -30,12 +32,75 @@ pub struct DownloadSettings {
     pub dry_run: bool,
     pub manifest_path: String,
     pub failure_manifest: String,
+    pub skip_near_duplicates: bool,
+    pub near_duplicate_threshold: u32,
+    /// Download posts even if the tracker lists them, e.g. to replace broken files.
+    pub ignore_tracker: bool,
+    /// After fetching the full result, report local files whose posts are no longer in it.
+    pub mirror: bool,
+    /// Refuse to start, instead of only warning, when the estimated size doesn't fit.
+    pub refuse_low_space: bool,
+    /// Bytes to keep free on the target drive; downloads pause below it. 0 disables.
+    pub min_free_space: u64,
+    pub quota: Quota,
+    /// Tag/favourite searches start next to this post id instead of at the newest post.
+    pub start_from: Option<crate::api::Cursor>,
+}
+
+/// Optional limits for one job; `None` means unlimited. They are checked before each
+/// post starts, so downloads already in flight may go slightly past them.
+#[derive(Clone, Copy, Default)]
+pub struct Quota {
+    pub max_bytes: Option<u64>,
+    /// New files only; skipped posts don't count.
+    pub max_files: Option<u64>,
+    pub max_duration: Option<Duration>,
 }
 
 pub enum JobKind {
-    Favourites,
+    /// Favourites of one or more users. Several users' favourites are merged so each
+    /// post is downloaded once; with `all` only posts every user favourited are kept.
+    /// `per_user` puts each post into `fav_<username>/` of the first listed user who
+    /// favourited it.
+    Favourites {
+        users: Vec<String>,
+        all: bool,
+        per_user: bool,
+    },
     Tags,
+    /// Posts matching any of these queries, searched one by one and downloaded once each.
+    Union(Vec<String>),
     Pool(u64),
+    /// Several pools, each downloaded into its own folder named after the pool and its id.
+    Pools(Vec<u64>),
+    /// A set by id or shortname; `ordered` numbers files in set order like a pool.
+    Set {
+        set: String,
+        ordered: bool,
+    },
+    /// Re-fetches tracked posts and downloads those whose file changed on the site.
+    /// `archive_old` moves the previous file (when its path was recorded) into `replaced/`
+    /// before the new one is downloaded, and back again if that download fails.
+    CheckReplacements {
+        archive_old: bool,
+    },
+    /// Exactly these posts; `md5s` are looked up first.
+    Posts {
+        ids: Vec<u64>,
+        md5s: Vec<String>,
+    },
+    /// Re-downloads the files of these audit issues. Each new file is checked against the
+    /// post's md5 before the old one is trashed and the new one takes its path.
+    Repair(Vec<crate::audit::AuditIssue>),
+    /// New posts of each artist tag, each into its `artists/<name>/` folder.
+    Artists(Vec<String>),
+    /// Popular posts of each listed date at `scale`, kept only if they match `filter`
+    /// (a search query; empty keeps everything).
+    Popular {
+        scale: crate::popular::Scale,
+        dates: Vec<String>,
+        filter: String,
+    },
     RetryFailed,
 }
 
@@ -47,14 +112,42 @@ pub enum Progress {
     /// One more post has finished (successfully or not).
     Tick(f64),
     Finished(DownloadStatistics),
+    /// A job that isn't a download finished; the text is shown to the user as-is.
+    Summary(String),
+    /// Groups of visually similar local files from a library report.
+    SimilarGroups(Vec<Vec<PathBuf>>),
+    /// Problems found by a library audit.
+    Audit(Vec<crate::audit::AuditIssue>),
+    /// Mirror mode: local files whose posts are no longer in the job's result.
+    Mirror(Vec<PathBuf>),
+    /// Something the user should know about; the job keeps running.
+    Warning(String),
+    /// A quota was reached; no further posts are started. The text names the limit.
+    LimitReached(String),
     Cancelled,
     Error(String),
 }
 
 pub enum ZipEvent {
+    /// A split archive moved on to volume `index` of `total` (1-based).
+    Volume {
+        index: usize,
+        total: usize,
+    },
     Finished(bool),
 }
 
+/// How [`spawn_zip`] divides the downloaded files between archives.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveSplit {
+    /// One archive containing the whole folder.
+    None,
+    /// At most this many files per volume.
+    Pages(usize),
+    /// At most this many bytes per volume (a single larger file still gets its own volume).
+    Size(u64),
+}
+
 /// Spawns a download job on its own thread. Progress/completion is reported via `tx`.
 /// `cancel` is checked between pages/posts so "Stop" can take effect promptly without
 /// aborting a file download mid-write.
@@ -96,49 +189,324 @@ pub fn spawn_download(
             username: settings.username.clone(),
             api_key: settings.api_key.clone(),
         };
-        let tracker = if settings.dry_run || settings.track_file.trim().is_empty() {
-            None
-        } else {
-            match Tracker::load(std::path::Path::new(&settings.track_file)) {
-                Ok(t) => Some(t),
-                Err(e) => {
-                    let _ = tx.send(Progress::Error(format!(
-                        "Failed to open tracking file {}: {e}",
-                        settings.track_file
-                    )));
-                    return;
+        let tracker =
+            if settings.dry_run || settings.ignore_tracker || settings.track_file.trim().is_empty()
+            {
+                None
+            } else {
+                match Tracker::load(std::path::Path::new(&settings.track_file)) {
+                    Ok(t) => Some(t),
+                    Err(e) => {
+                        let _ = tx.send(Progress::Error(format!(
+                            "Failed to open tracking file {}: {e}",
+                            settings.track_file
+                        )));
+                        return;
+                    }
                 }
-            }
-        };
+            };
+        let limits = JobLimits::new(&settings);
         if matches!(kind, JobKind::RetryFailed) {
             run_retry_failed(&settings, &output_dir, &cancel, &tx, tracker.as_ref());
             return;
         }
+        if let JobKind::Repair(issues) = &kind {
+            if settings.dry_run {
+                let _ = tx.send(Progress::Summary(format!(
+                    "Dry run: {} files would be downloaded again.",
+                    issues.len()
+                )));
+            } else {
+                run_repair(&context, &login, issues, &cancel, &tx);
+            }
+            return;
+        }
         let client = get_client();
-        let random_check = if settings.random { "order:random" } else { "" };
+        let order = settings
+            .order
+            .as_ref()
+            .map(|order| format!("order:{order}"))
+            .unwrap_or_default();
 
-        let pages: Vec<Vec<Post>> = match &kind {
-            JobKind::Favourites => {
-                let fav = format!("fav:{}", settings.username);
-                get_pages(
-                    &context,
-                    &login,
-                    &client,
-                    &fav,
-                    &settings.tags,
-                    random_check,
-                    &settings.count,
-                )
+        // Subfolders of `output_dir` for the pages at the same index; empty means none.
+        let mut page_folders: Vec<PathBuf> = Vec::new();
+        // Artist of the page at the same index, and each artist's fetched range once all
+        // of their pages are done, for artist jobs.
+        let mut page_artists: Vec<String> = Vec::new();
+        let mut planned_ranges: std::collections::HashMap<String, crate::watchlist::FetchedRange> =
+            std::collections::HashMap::new();
+        // Files to archive once a replacement check downloaded their new versions.
+        let mut superseded = Superseded::new();
+        let mut pages: Vec<Vec<Post>> = match &kind {
+            JobKind::Favourites {
+                users,
+                all,
+                per_user,
+            } => {
+                // Intersecting needs every user's whole list; the page limit applies to
+                // the merged result instead.
+                let mut search_settings = settings.clone();
+                if *all {
+                    search_settings.pages = -1;
+                }
+                let mut found = Vec::new();
+                for (i, user) in users.iter().enumerate() {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if users.len() > 1 {
+                        let _ = tx.send(Progress::Status(format!(
+                            "Fetching favourites of {user} ({}/{})...",
+                            i + 1,
+                            users.len()
+                        )));
+                    }
+                    let pages = match search_pages(
+                        &context,
+                        &login,
+                        &client,
+                        &format!("fav:{user}"),
+                        &settings.tags,
+                        &order,
+                        &search_settings,
+                        &cancel,
+                        &tx,
+                    ) {
+                        Ok(pages) => pages,
+                        Err(error) => {
+                            let _ =
+                                tx.send(Progress::Error(format!("Favourites of {user}: {error}")));
+                            return;
+                        }
+                    };
+                    found.push((user.as_str(), pages));
+                }
+                let (mut pages, folders) = merge_favourites(found, *all, *per_user);
+                if *all && settings.pages >= 0 {
+                    pages.truncate(usize::try_from(settings.pages).unwrap_or(usize::MAX));
+                }
+                page_folders = folders;
+                pages
             }
-            JobKind::Tags => get_pages(
+            JobKind::Tags => match search_pages(
                 &context,
                 &login,
                 &client,
                 "",
                 &settings.tags,
-                random_check,
-                &settings.count,
-            ),
+                &order,
+                &settings,
+                &cancel,
+                &tx,
+            ) {
+                Ok(pages) => pages,
+                Err(error) => {
+                    let _ = tx.send(Progress::Error(error));
+                    return;
+                }
+            },
+            JobKind::Artists(names) => {
+                let fetched = crate::watchlist::FetchedIds::load(
+                    &crate::watchlist::FetchedIds::path(&output_dir),
+                )
+                .unwrap_or_else(|error| {
+                    let _ = tx.send(Progress::Warning(format!(
+                        "{error}; searching every artist from the start."
+                    )));
+                    crate::watchlist::FetchedIds::default()
+                });
+                let mut pages = Vec::new();
+                'artists: for (i, name) in names.iter().enumerate() {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let _ = tx.send(Progress::Status(format!(
+                        "Searching {name} ({}/{})...",
+                        i + 1,
+                        names.len()
+                    )));
+                    // Everything above the fetched range is new and all of it is wanted;
+                    // below it, posts an earlier page limit cut off are walked down further
+                    // within this job's page limit.
+                    let range = fetched.artists.get(name).copied();
+                    let mut searches = Vec::new();
+                    if let Some(range) = range {
+                        let mut newer = settings.clone();
+                        newer.start_from = Some(crate::api::Cursor::After(range.newest));
+                        newer.pages = -1;
+                        searches.push((newer, false));
+                    }
+                    match range {
+                        None => searches.push((settings.clone(), true)),
+                        Some(crate::watchlist::FetchedRange {
+                            oldest: Some(oldest),
+                            ..
+                        }) => {
+                            let mut older = settings.clone();
+                            older.start_from = Some(crate::api::Cursor::Before(oldest));
+                            searches.push((older, true));
+                        }
+                        Some(_) => {}
+                    }
+                    let mut next = range.unwrap_or(crate::watchlist::FetchedRange {
+                        newest: 0,
+                        oldest: None,
+                    });
+                    let mut artist_pages = Vec::new();
+                    for (search_settings, downwards) in searches {
+                        let found = match search_pages(
+                            &context,
+                            &login,
+                            &client,
+                            "",
+                            name,
+                            &order,
+                            &search_settings,
+                            &cancel,
+                            &tx,
+                        ) {
+                            Ok(found) => found,
+                            // One failing artist shouldn't hold back the rest of the watchlist.
+                            Err(error) => {
+                                let _ = tx.send(Progress::Warning(format!("{name}: {error}")));
+                                continue 'artists;
+                            }
+                        };
+                        let ids = found.iter().flatten().map(|post| post.id);
+                        next.newest = next.newest.max(ids.clone().max().unwrap_or_default());
+                        if downwards {
+                            // A short last page means the search reached the artist's
+                            // first post.
+                            let exhausted = found
+                                .last()
+                                .is_none_or(|page| page.len() < settings.count as usize);
+                            next.oldest = if exhausted {
+                                None
+                            } else {
+                                ids.min().or(next.oldest)
+                            };
+                        }
+                        artist_pages.extend(found);
+                    }
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    planned_ranges.insert(name.clone(), next);
+                    for page in artist_pages {
+                        page_folders.push(crate::watchlist::folder(name));
+                        page_artists.push(name.clone());
+                        pages.push(page);
+                    }
+                }
+                pages
+            }
+            JobKind::Union(queries) => {
+                let mut seen = std::collections::HashSet::new();
+                let mut merged = Vec::new();
+                for (i, query) in queries.iter().enumerate() {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let _ = tx.send(Progress::Status(format!(
+                        "Searching query {}/{}...",
+                        i + 1,
+                        queries.len()
+                    )));
+                    let pages = match search_pages(
+                        &context, &login, &client, "", query, &order, &settings, &cancel, &tx,
+                    ) {
+                        Ok(pages) => pages,
+                        Err(error) => {
+                            let _ = tx.send(Progress::Error(format!(
+                                "Query {} ({query}): {error}",
+                                i + 1
+                            )));
+                            return;
+                        }
+                    };
+                    for page in pages {
+                        let page: Vec<Post> = page
+                            .into_iter()
+                            .filter(|post| seen.insert(post.id))
+                            .collect();
+                        if !page.is_empty() {
+                            merged.push(page);
+                        }
+                    }
+                }
+                merged
+            }
+            JobKind::Popular {
+                scale,
+                dates,
+                filter,
+            } => {
+                let mut throttle = crate::api::Throttle::default();
+                throttle.wait();
+                let blacklist = match crate::api::blacklist(settings.nsfw, &login) {
+                    Ok(text) => crate::popular::Blacklist::parse(&text),
+                    Err(error) => {
+                        let _ = tx.send(Progress::Warning(format!(
+                            "Could not load your blacklist, nothing is hidden: {error}"
+                        )));
+                        crate::popular::Blacklist::default()
+                    }
+                };
+                if !blacklist.ignored.is_empty() {
+                    let _ = tx.send(Progress::Warning(format!(
+                        "Blacklist lines with metatags other than rating: can't be checked here \
+                         and are not applied: {}",
+                        blacklist.ignored.join(", ")
+                    )));
+                }
+                let mut seen = std::collections::HashSet::new();
+                let mut pages = Vec::new();
+                let mut hidden = 0;
+                for (i, date) in dates.iter().enumerate() {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let _ = tx.send(Progress::Status(format!(
+                        "Fetching popular posts of {date} ({}/{})...",
+                        i + 1,
+                        dates.len()
+                    )));
+                    throttle.wait();
+                    let fetched =
+                        crate::api::popular_posts(settings.nsfw, &login, date, *scale, &blacklist)
+                            .and_then(|(posts, blacklisted)| {
+                                hidden += blacklisted;
+                                let posts: Vec<Post> = posts
+                                    .into_iter()
+                                    .filter(|post| seen.insert(post.id))
+                                    .collect();
+                                if filter.trim().is_empty() || posts.is_empty() {
+                                    Ok(posts)
+                                } else {
+                                    crate::api::filter_posts(
+                                        settings.nsfw,
+                                        &login,
+                                        posts,
+                                        filter,
+                                        &mut throttle,
+                                    )
+                                }
+                            });
+                    match fetched {
+                        Ok(posts) if posts.is_empty() => {}
+                        Ok(posts) => pages.push(posts),
+                        Err(error) => {
+                            let _ = tx.send(Progress::Warning(format!("{date}: {error}")));
+                        }
+                    }
+                }
+                if hidden > 0 {
+                    let _ = tx.send(Progress::Warning(format!(
+                        "Skipped {hidden} posts your blacklist hides."
+                    )));
+                }
+                pages
+            }
             JobKind::Pool(pool_id) => {
                 let Some(pool) = get_pool(&context, &client, &login, pool_id) else {
                     let _ = tx.send(Progress::Error("Pool not found.".into()));
@@ -149,20 +517,143 @@ pub fn spawn_download(
                     let _ = tx.send(Progress::Error("Pool has no downloadable posts.".into()));
                     return;
                 }
-                run_indexed_download(
+                if !preflight_space(posts.iter(), &settings, &output_dir, &tx) {
+                    return;
+                }
+                let _ = tx.send(Progress::Total(posts.len() as u64));
+                let statistics = run_indexed_download(
                     posts,
+                    &client,
+                    &login,
+                    &context,
+                    &output_dir,
+                    &limits,
+                    &cancel,
+                    &tx,
+                    tracker.as_ref(),
+                );
+                finish_download(&settings, &output_dir, &context, statistics, &tx);
+                return;
+            }
+            JobKind::Pools(pool_ids) => {
+                run_pools(
+                    pool_ids,
                     &settings,
                     &client,
                     &login,
                     &context,
                     &output_dir,
+                    &limits,
                     &cancel,
                     &tx,
                     tracker.as_ref(),
                 );
                 return;
             }
-            JobKind::RetryFailed => unreachable!(),
+            JobKind::Set { set, ordered } => {
+                let post_set = match crate::api::get_set(settings.nsfw, &login, set) {
+                    Ok(post_set) => post_set,
+                    Err(error) => {
+                        let _ = tx.send(Progress::Error(error));
+                        return;
+                    }
+                };
+                let _ = tx.send(Progress::Status(format!(
+                    "Fetching {} posts of set '{}'...",
+                    post_set.post_ids.len(),
+                    post_set.name
+                )));
+                let posts = get_post_data(&context, &client, &login, &post_set.post_ids);
+                if posts.is_empty() {
+                    let _ = tx.send(Progress::Error("Set has no downloadable posts.".into()));
+                    return;
+                }
+                if *ordered && !settings.dry_run {
+                    if !preflight_space(posts.iter(), &settings, &output_dir, &tx) {
+                        return;
+                    }
+                    let _ = tx.send(Progress::Total(posts.len() as u64));
+                    let statistics = run_indexed_download(
+                        posts,
+                        &client,
+                        &login,
+                        &context,
+                        &output_dir,
+                        &limits,
+                        &cancel,
+                        &tx,
+                        tracker.as_ref(),
+                    );
+                    finish_download(&settings, &output_dir, &context, statistics, &tx);
+                    return;
+                }
+                vec![posts]
+            }
+            JobKind::Posts { ids, md5s } => {
+                let mut ids = ids.clone();
+                if !md5s.is_empty() {
+                    let _ = tx.send(Progress::Status(format!(
+                        "Looking up {} md5s...",
+                        md5s.len()
+                    )));
+                    match crate::api::resolve_md5s(
+                        settings.nsfw,
+                        &login,
+                        md5s,
+                        &cancel,
+                        &mut crate::api::Throttle::default(),
+                    ) {
+                        Ok(found) => {
+                            // Keep the pasted order.
+                            for md5 in md5s {
+                                match found.get(md5) {
+                                    Some(id) if !ids.contains(id) => ids.push(*id),
+                                    _ => {}
+                                }
+                            }
+                        }
+                        Err(error) => {
+                            let _ = tx.send(Progress::Error(error));
+                            return;
+                        }
+                    }
+                    if cancel.load(Ordering::Relaxed) {
+                        let _ = tx.send(Progress::Cancelled);
+                        return;
+                    }
+                }
+                let posts = get_post_data(&context, &client, &login, &ids);
+                if posts.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![posts]
+                }
+            }
+            JobKind::CheckReplacements { archive_old } => {
+                match find_replacements(
+                    &settings,
+                    &output_dir,
+                    *archive_old,
+                    &context,
+                    &client,
+                    &login,
+                    &tx,
+                ) {
+                    Ok((posts, _)) if posts.is_empty() => {
+                        let _ = tx.send(Progress::Summary("No replaced posts found.".to_owned()));
+                        return;
+                    }
+                    Ok((posts, old_files)) => {
+                        superseded = old_files;
+                        vec![posts]
+                    }
+                    Err(error) => {
+                        let _ = tx.send(Progress::Error(error));
+                        return;
+                    }
+                }
+            }
+            JobKind::RetryFailed | JobKind::Repair(_) => unreachable!(),
         };
 
         if pages.is_empty() {
@@ -175,9 +666,29 @@ pub fn spawn_download(
         }
 
         let total: usize = pages.iter().map(|p| p.len()).sum();
+        if !preflight_space(pages.iter().flatten(), &settings, &output_dir, &tx) {
+            return;
+        }
         let _ = tx.send(Progress::Total(total as u64));
 
+        // Mirroring only makes sense against the complete result, never a page subset.
+        let mirror = (settings.mirror && settings.pages == -1 && settings.start_from.is_none())
+            .then(|| mirror_query(&kind, &settings.tags))
+            .flatten()
+            .map(|query| {
+                let md5s: std::collections::HashMap<u64, String> = pages
+                    .iter()
+                    .flatten()
+                    .map(|post| (post.id, post.file.md5.clone()))
+                    .collect();
+                (query, md5s)
+            });
+
         if settings.dry_run {
+            if let Some((query, md5s)) = &mirror {
+                let keep = md5s.values().cloned().collect();
+                report_mirror(&settings, &output_dir, &page_folders, query, &keep, &tx);
+            }
             let estimated = pages
                 .iter()
                 .flatten()
@@ -208,10 +719,38 @@ pub fn spawn_download(
 
         let pool = rayon_pool(context.num_threads);
 
-        for posts in pages {
-            if cancel.load(Ordering::Relaxed) {
+        let mut pending_hashes = None;
+        if settings.skip_near_duplicates {
+            let (dropped, pending) = skip_near_duplicates(
+                &mut pages,
+                &settings,
+                &output_dir,
+                &client,
+                &pool,
+                &cancel,
+                &tx,
+            );
+            skipped += dropped;
+            pending_hashes = pending;
+        }
+
+        let archived = archive_superseded(&output_dir, &superseded, &tx);
+
+        // Pages whose posts were all downloaded or skipped.
+        let mut complete_pages = vec![false; pages.len()];
+        for (i, posts) in pages.into_iter().enumerate() {
+            if cancel.load(Ordering::Relaxed) || !limits.allows_more(&tx) {
                 break;
             }
+            let page_len = posts.len() as i64;
+            let page_dir = match page_folders.get(i) {
+                Some(folder) => {
+                    let dir = output_dir.join(folder);
+                    funcs::ensure_dl_dir(&dir);
+                    dir
+                }
+                None => output_dir.clone(),
+            };
 
             let (page_finished, page_failed, page_skipped, page_bytes, page_records) =
                 download_posts_parallel(
@@ -219,19 +758,47 @@ pub fn spawn_download(
                     &client,
                     &login,
                     &context,
-                    &output_dir,
+                    &page_dir,
                     posts,
+                    &limits,
                     &cancel,
                     &tx,
                     tracker.as_ref(),
                 );
 
+            if let Some(pending) = &mut pending_hashes {
+                pending.record(&page_records);
+            }
+            if page_failed == 0 && page_finished + page_skipped == page_len {
+                complete_pages[i] = true;
+            }
             completed += page_finished;
             failed += page_failed;
             skipped += page_skipped;
             downloaded_amount += page_bytes;
             records.extend(page_records);
         }
+        if let Some(pending) = &pending_hashes {
+            pending.save(&tx);
+        }
+        restore_unreplaced(&archived, &records, &tx);
+        if !planned_ranges.is_empty() {
+            record_fetched_ranges(
+                &output_dir,
+                planned_ranges,
+                &page_artists,
+                &complete_pages,
+                &tx,
+            );
+        }
+
+        if let Some((query, md5s)) = &mirror {
+            record_mirror(&output_dir, query, md5s, &records, &tx);
+            if !cancel.load(Ordering::Relaxed) {
+                let keep = md5s.values().cloned().collect();
+                report_mirror(&settings, &output_dir, &page_folders, query, &keep, &tx);
+            }
+        }
 
         finish_download(
             &settings,
@@ -250,22 +817,284 @@ pub fn spawn_download(
     })
 }
 
+/// The site refuses numbered pages past this one.
+const NUMBERED_PAGE_LIMIT: i64 = 750;
+
+/// Searches `prefix tags order`. Searches in post id order (the default newest first, or
+/// `order:id_desc`/`order:id`) that want every page, more pages than the site numbers, or
+/// a start id page by post id via [`crate::api::search_posts`]; everything else goes
+/// through e-cli's numbered pages, which is also the only way to page other orders.
+/// Paging by post id fails when the first page does; a later failure keeps the pages so far.
 #[allow(clippy::too_many_arguments)]
-fn run_indexed_download(
-    posts: Vec<Post>,
+fn search_pages(
+    context: &CliContext,
+    login: &Login,
+    client: &reqwest::blocking::Client,
+    prefix: &str,
+    tags: &str,
+    order: &str,
+    settings: &DownloadSettings,
+    cancel: &AtomicBool,
+    tx: &Sender<Progress>,
+) -> Result<Vec<Vec<Post>>, String> {
+    let tokens = crate::query::tokenize(tags);
+    let order_value = order.strip_prefix("order:").map(str::to_owned).or_else(|| {
+        tokens
+            .iter()
+            .rev()
+            .find(|token| token.metatag_name() == Some("order"))
+            .map(|token| token.name["order:".len()..].to_owned())
+    });
+    // Whether the order can be paged by post id, and if so whether upwards.
+    let ascending = match order_value.as_deref() {
+        None | Some("id_desc") => Some(false),
+        Some("id") => Some(true),
+        Some(_) => None,
+    };
+    let deep = settings.pages == -1 || settings.pages > NUMBERED_PAGE_LIMIT;
+    let Some(ascending) = ascending.filter(|_| deep || settings.start_from.is_some()) else {
+        if ascending.is_none() && settings.start_from.is_some() {
+            let _ = tx.send(Progress::Warning(
+                "Sorted searches can't start at a post id; starting at the first page.".into(),
+            ));
+        }
+        if ascending.is_none() && deep {
+            let _ = tx.send(Progress::Warning(format!(
+                "Sorted searches end after the site's {NUMBERED_PAGE_LIMIT} numbered pages."
+            )));
+        }
+        let mut pages = get_pages(context, login, client, prefix, tags, order, &settings.count);
+        if order == "order:random" {
+            // Every random page is drawn anew, so later pages can repeat earlier posts.
+            let mut seen = std::collections::HashSet::new();
+            for page in &mut pages {
+                page.retain(|post| seen.insert(post.id));
+            }
+            pages.retain(|page| !page.is_empty());
+        }
+        return Ok(pages);
+    };
+
+    // The cursor sets the order, so `order:` tags are left out of the query.
+    let tags: Vec<&str> = tokens
+        .iter()
+        .filter(|token| token.metatag_name() != Some("order"))
+        .map(|token| &tags[token.start..token.end])
+        .collect();
+    let query = format!("{prefix} {}", tags.join(" ")).trim().to_owned();
+    let mut cursor = match (ascending, settings.start_from) {
+        // Oldest first walks upwards from the start id, or from the very first post.
+        (true, Some(crate::api::Cursor::Before(id) | crate::api::Cursor::After(id))) => {
+            Some(crate::api::Cursor::After(id))
+        }
+        (true, None) => Some(crate::api::Cursor::After(0)),
+        (false, start_from) => start_from,
+    };
+    let mut pages: Vec<Vec<Post>> = Vec::new();
+    while settings.pages == -1 || (pages.len() as i64) < settings.pages {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        if !pages.is_empty() {
+            thread::sleep(crate::api::REQUEST_DELAY);
+        }
+        let _ = tx.send(Progress::Status(format!(
+            "Fetching page {} by post id...",
+            pages.len() + 1
+        )));
+        let mut posts =
+            match crate::api::search_posts(settings.nsfw, login, &query, settings.count, cursor) {
+                Ok(posts) => posts,
+                Err(error) if pages.is_empty() => return Err(error),
+                Err(error) => {
+                    let _ = tx.send(Progress::Warning(format!(
+                        "Stopped after {} pages: {error}",
+                        pages.len()
+                    )));
+                    break;
+                }
+            };
+        if posts.is_empty() {
+            break;
+        }
+        let ids = posts.iter().map(|post| post.id);
+        cursor = Some(match cursor {
+            Some(crate::api::Cursor::After(_)) => {
+                crate::api::Cursor::After(ids.max().unwrap_or_default())
+            }
+            _ => crate::api::Cursor::Before(ids.min().unwrap_or_default()),
+        });
+        if ascending {
+            posts.sort_by_key(|post| post.id);
+        }
+        pages.push(posts);
+    }
+    Ok(pages)
+}
+
+/// Combines the favourites of several users (in the order given) into one list of pages
+/// without repeats. With `all` only posts in every user's favourites are kept; otherwise
+/// `per_user` also returns a `fav_<username>` folder for each page.
+fn merge_favourites(
+    found: Vec<(&str, Vec<Vec<Post>>)>,
+    all: bool,
+    per_user: bool,
+) -> (Vec<Vec<Post>>, Vec<PathBuf>) {
+    use std::collections::HashSet;
+
+    let mut seen = HashSet::new();
+    let mut pages = Vec::new();
+    let mut folders = Vec::new();
+    if all {
+        let favourited: Vec<HashSet<u64>> = found
+            .iter()
+            .map(|(_, pages)| pages.iter().flatten().map(|post| post.id).collect())
+            .collect();
+        if let Some((_, first)) = found.into_iter().next() {
+            for page in first {
+                let page: Vec<Post> = page
+                    .into_iter()
+                    .filter(|post| favourited.iter().all(|ids| ids.contains(&post.id)))
+                    .filter(|post| seen.insert(post.id))
+                    .collect();
+                if !page.is_empty() {
+                    pages.push(page);
+                }
+            }
+        }
+        return (pages, folders);
+    }
+    for (user, user_pages) in found {
+        for page in user_pages {
+            let page: Vec<Post> = page
+                .into_iter()
+                .filter(|post| seen.insert(post.id))
+                .collect();
+            if !page.is_empty() {
+                pages.push(page);
+                if per_user {
+                    folders.push(PathBuf::from(crate::format::folder_name(
+                        &format!("fav_{user}"),
+                        "fav_",
+                    )));
+                }
+            }
+        }
+    }
+    (pages, folders)
+}
+
+/// Resolves every pool up front so the UI gets one combined total, then downloads each
+/// pool into `output_dir/<pool name> (#<id>)` with its own reading-order numbering.
+/// Pools that can't be found are skipped with a warning.
+#[allow(clippy::too_many_arguments)]
+fn run_pools(
+    pool_ids: &[u64],
     settings: &DownloadSettings,
     client: &reqwest::blocking::Client,
     login: &Login,
     context: &CliContext,
     output_dir: &std::path::Path,
+    limits: &JobLimits,
     cancel: &Arc<AtomicBool>,
     tx: &Sender<Progress>,
     tracker: Option<&Tracker>,
 ) {
+    let mut resolved = Vec::new();
+    for (i, pool_id) in pool_ids.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            let _ = tx.send(Progress::Cancelled);
+            return;
+        }
+        let _ = tx.send(Progress::Status(format!(
+            "Fetching pool {}/{} (#{pool_id})...",
+            i + 1,
+            pool_ids.len()
+        )));
+        let Some(pool) = get_pool(context, client, login, pool_id) else {
+            let _ = tx.send(Progress::Warning(format!(
+                "Pool #{pool_id} not found; skipping it."
+            )));
+            continue;
+        };
+        let posts = get_post_data(context, client, login, &pool.post_ids);
+        resolved.push((pool_folder_name(&pool.name, *pool_id), posts));
+    }
+
+    let total: usize = resolved.iter().map(|(_, posts)| posts.len()).sum();
+    if total == 0 {
+        let _ = tx.send(Progress::Error("Pools have no downloadable posts.".into()));
+        return;
+    }
+    let all_posts = resolved.iter().flat_map(|(_, posts)| posts);
+    if !preflight_space(all_posts, settings, output_dir, tx) {
+        return;
+    }
+    let _ = tx.send(Progress::Total(total as u64));
+
+    let mut statistics = DownloadStatistics {
+        completed: 0,
+        failed: 0,
+        skipped: 0,
+        total,
+        downloaded_amount: 0.0,
+        records: Vec::new(),
+    };
+    let pool_count = resolved.len();
+    for (i, (folder, posts)) in resolved.into_iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) || !limits.allows_more(tx) {
+            break;
+        }
+        let _ = tx.send(Progress::Status(format!(
+            "Pool {}/{pool_count}: {folder}",
+            i + 1
+        )));
+        let pool_statistics = run_indexed_download(
+            posts,
+            client,
+            login,
+            context,
+            &output_dir.join(&folder),
+            limits,
+            cancel,
+            tx,
+            tracker,
+        );
+        statistics.completed += pool_statistics.completed;
+        statistics.failed += pool_statistics.failed;
+        statistics.skipped += pool_statistics.skipped;
+        statistics.downloaded_amount += pool_statistics.downloaded_amount;
+        statistics.records.extend(pool_statistics.records);
+    }
+    finish_download(settings, output_dir, context, statistics, tx);
+}
+
+/// Turns a pool name into a folder name that is valid on Windows and Linux. The id keeps
+/// pools that share a name from numbering into the same folder.
+fn pool_folder_name(name: &str, id: u64) -> String {
+    format!(
+        "{} (#{id})",
+        crate::format::folder_name(&name.replace('_', " "), "pool")
+    )
+}
+
+/// Downloads `posts` into `output_dir`, numbering files by their position so reading
+/// order survives. The caller reports the total and finishes the job.
+#[allow(clippy::too_many_arguments)]
+fn run_indexed_download(
+    posts: Vec<Post>,
+    client: &reqwest::blocking::Client,
+    login: &Login,
+    context: &CliContext,
+    output_dir: &std::path::Path,
+    limits: &JobLimits,
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+    tracker: Option<&Tracker>,
+) -> DownloadStatistics {
     funcs::ensure_dl_dir(output_dir);
 
     let total = posts.len();
-    let _ = tx.send(Progress::Total(total as u64));
 
     let pool = rayon_pool(context.num_threads);
     let indexed: Vec<(u64, Post)> = posts
@@ -288,7 +1117,8 @@ fn run_indexed_download(
             indexed.into_par_iter().for_each_with(
                 (tx_chunk, tx.clone()),
                 |(chunk_tx, progress_tx), (index, post)| {
-                    if cancel.load(Ordering::Relaxed) {
+                    wait_for_space(output_dir, limits.min_free_space, cancel, progress_tx);
+                    if cancel.load(Ordering::Relaxed) || !limits.allows_more(progress_tx) {
                         return;
                     }
                     let result = funcs::download_with_options(
@@ -305,6 +1135,7 @@ fn run_indexed_download(
                             cancel: Some(cancel.clone()),
                         },
                     );
+                    limits.record(result.amount, result.amount_finished);
                     let _ = progress_tx.send(Progress::Tick(result.amount));
                     let _ = chunk_tx.send(result);
                 },
@@ -319,20 +1150,163 @@ fn run_indexed_download(
         }
     }
 
-    finish_download(
-        settings,
-        output_dir,
-        context,
-        DownloadStatistics {
-            completed,
-            failed,
-            skipped,
-            total,
-            downloaded_amount,
-            records,
-        },
-        tx,
+    DownloadStatistics {
+        completed,
+        failed,
+        skipped,
+        total,
+        downloaded_amount,
+        records,
+    }
+}
+
+/// State shared by every worker of one job: the free-space reserve and the quota
+/// counters.
+struct JobLimits {
+    min_free_space: u64,
+    quota: Quota,
+    started: Instant,
+    bytes: AtomicU64,
+    files: AtomicU64,
+    reached: AtomicBool,
+}
+
+impl JobLimits {
+    fn new(settings: &DownloadSettings) -> Self {
+        Self {
+            min_free_space: settings.min_free_space,
+            quota: settings.quota,
+            started: Instant::now(),
+            bytes: AtomicU64::new(0),
+            files: AtomicU64::new(0),
+            reached: AtomicBool::new(false),
+        }
+    }
+
+    fn record(&self, bytes: f64, finished: i64) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.files
+            .fetch_add(finished.max(0) as u64, Ordering::Relaxed);
+    }
+
+    /// Whether another post may start. The first worker to find a limit reached reports
+    /// it with `LimitReached`.
+    fn allows_more(&self, tx: &Sender<Progress>) -> bool {
+        if self.reached.load(Ordering::Relaxed) {
+            return false;
+        }
+        let Some(limit) = self.exceeded() else {
+            return true;
+        };
+        if !self.reached.swap(true, Ordering::Relaxed) {
+            let _ = tx.send(Progress::LimitReached(limit));
+        }
+        false
+    }
+
+    fn exceeded(&self) -> Option<String> {
+        if let Some(max) = self.quota.max_bytes {
+            if self.bytes.load(Ordering::Relaxed) >= max {
+                return Some(format!(
+                    "{} size limit",
+                    crate::format::format_bytes(max as f64)
+                ));
+            }
+        }
+        if let Some(max) = self.quota.max_files {
+            if self.files.load(Ordering::Relaxed) >= max {
+                return Some(format!("{max} file limit"));
+            }
+        }
+        if let Some(max) = self.quota.max_duration {
+            if self.started.elapsed() >= max {
+                return Some(format!(
+                    "{} time limit",
+                    crate::format::format_duration(max)
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Free space on the drive holding `dir`, looking at the closest existing parent when
+/// `dir` hasn't been created yet (e.g. in a dry run).
+fn free_space(dir: &std::path::Path) -> Option<u64> {
+    let existing = dir.ancestors().find(|path| path.exists())?;
+    fs4::available_space(existing).ok()
+}
+
+/// Compares the size of the `posts` still due plus the free-space reserve with what is
+/// free on the target drive. Posts already in the duplicate index or the track file are
+/// skipped by the download, so they don't count, and a size quota caps the total. Warns
+/// when it doesn't fit, or refuses with an `Error` and returns false when the settings
+/// ask for that. Dry runs only ever warn.
+fn preflight_space<'a>(
+    posts: impl Iterator<Item = &'a Post>,
+    settings: &DownloadSettings,
+    output_dir: &std::path::Path,
+    tx: &Sender<Progress>,
+) -> bool {
+    let Some(available) = free_space(output_dir) else {
+        return true;
+    };
+    let index_path = crate::library::index_path(&settings.duplicate_index, output_dir);
+    let index = e_cli::duplicate::DuplicateIndex::load(&index_path).unwrap_or_default();
+    let tracked = if settings.ignore_tracker {
+        std::collections::HashSet::new()
+    } else {
+        crate::import::tracked_ids(&settings.track_file)
+    };
+    let needed: u64 = posts
+        .filter(|post| !tracked.contains(&post.id) && !index.contains(&post.file.md5))
+        .filter_map(|post| post.file.size)
+        .fold(0, u64::saturating_add)
+        .min(settings.quota.max_bytes.unwrap_or(u64::MAX));
+    if needed.saturating_add(settings.min_free_space) <= available {
+        return true;
+    }
+    let message = format!(
+        "About {} to download, but only {} is free on the target drive.",
+        crate::format::format_bytes(needed as f64),
+        crate::format::format_bytes(available as f64)
     );
+    if settings.refuse_low_space && !settings.dry_run {
+        let _ = tx.send(Progress::Error(message));
+        false
+    } else {
+        let _ = tx.send(Progress::Warning(message));
+        true
+    }
+}
+
+/// Blocks a worker while free space on the target drive is below `min_free_space`, so
+/// the job resumes by itself once space is freed. Cancelling ends the wait.
+fn wait_for_space(
+    output_dir: &std::path::Path,
+    min_free_space: u64,
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+) {
+    if min_free_space == 0 {
+        return;
+    }
+    let mut paused = false;
+    while !cancel.load(Ordering::Relaxed)
+        && free_space(output_dir).is_some_and(|free| free < min_free_space)
+    {
+        if !paused {
+            paused = true;
+            let _ = tx.send(Progress::Status(format!(
+                "Paused: less than {} free on the target drive.",
+                crate::format::format_bytes(min_free_space as f64)
+            )));
+        }
+        thread::sleep(Duration::from_secs(5));
+    }
+    if paused {
+        let _ = tx.send(Progress::Status("Downloading posts...".to_owned()));
+    }
 }
 
 fn rayon_pool(num_threads: usize) -> rayon::ThreadPool {
@@ -350,6 +1324,7 @@ fn download_posts_parallel(
     context: &CliContext,
     output_dir: &std::path::Path,
     posts: Vec<Post>,
+    limits: &JobLimits,
     cancel: &Arc<AtomicBool>,
     tx: &Sender<Progress>,
     tracker: Option<&Tracker>,
@@ -363,7 +1338,8 @@ fn download_posts_parallel(
         posts.into_par_iter().for_each_with(
             (chunk_tx, tx.clone()),
             |(result_tx, progress_tx), post| {
-                if cancel.load(Ordering::Relaxed) {
+                wait_for_space(output_dir, limits.min_free_space, cancel, progress_tx);
+                if cancel.load(Ordering::Relaxed) || !limits.allows_more(progress_tx) {
                     return;
                 }
                 let result = funcs::download_with_options(
@@ -380,6 +1356,7 @@ fn download_posts_parallel(
                         cancel: Some(cancel.clone()),
                     },
                 );
+                limits.record(result.amount, result.amount_finished);
                 let _ = progress_tx.send(Progress::Tick(result.amount));
                 let _ = result_tx.send(result);
             },
@@ -401,16 +1378,396 @@ fn download_posts_parallel(
     (finished, failed, skipped, amount, records)
 }
 
+fn report_mirror(
+    settings: &DownloadSettings,
+    output_dir: &std::path::Path,
+    page_folders: &[PathBuf],
+    query: &str,
+    keep: &std::collections::HashSet<String>,
+    tx: &Sender<Progress>,
+) {
+    let _ = tx.send(Progress::Status(
+        "Comparing local files with the result...".into(),
+    ));
+    let ledger_path = crate::library::MirrorLedger::path(output_dir);
+    let ledger = match crate::library::MirrorLedger::load(&ledger_path) {
+        Ok(ledger) => ledger,
+        Err(error) => {
+            let _ = tx.send(Progress::Warning(error));
+            return;
+        }
+    };
+    let stale = ledger.stale(query, keep);
+    let mut dirs = vec![output_dir.to_path_buf()];
+    for folder in page_folders {
+        let dir = output_dir.join(folder);
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    let index_path = crate::library::index_path(&settings.duplicate_index, output_dir);
+    let files = if stale.is_empty() {
+        Vec::new()
+    } else {
+        crate::library::stale_files(&dirs, &index_path, &stale)
+    };
+    let _ = tx.send(Progress::Mirror(files));
+}
+
+/// Stores the fetched range of every artist whose pages in this job were all completed.
+fn record_fetched_ranges(
+    output_dir: &std::path::Path,
+    mut planned: std::collections::HashMap<String, crate::watchlist::FetchedRange>,
+    page_artists: &[String],
+    complete_pages: &[bool],
+    tx: &Sender<Progress>,
+) {
+    for (i, name) in page_artists.iter().enumerate() {
+        if !complete_pages.get(i).copied().unwrap_or_default() {
+            planned.remove(name);
+        }
+    }
+    if planned.is_empty() {
+        return;
+    }
+    let path = crate::watchlist::FetchedIds::path(output_dir);
+    let Ok(mut fetched) = crate::watchlist::FetchedIds::load(&path) else {
+        return;
+    };
+    fetched.artists.extend(planned);
+    if let Err(error) = fetched.save(&path) {
+        let _ = tx.send(Progress::Warning(error));
+    }
+}
+
+/// Adds the md5s of the posts in `records` to `query`'s entry in the mirror ledger.
+fn record_mirror(
+    output_dir: &std::path::Path,
+    query: &str,
+    md5s: &std::collections::HashMap<u64, String>,
+    records: &[e_cli::DownloadRecord],
+    tx: &Sender<Progress>,
+) {
+    let ledger_path = crate::library::MirrorLedger::path(output_dir);
+    let result = crate::library::MirrorLedger::load(&ledger_path).and_then(|mut ledger| {
+        ledger.queries.entry(query.to_owned()).or_default().extend(
+            records
+                .iter()
+                .filter_map(|record| md5s.get(&record.post_id).cloned()),
+        );
+        ledger.save(&ledger_path)
+    });
+    if let Err(error) = result {
+        let _ = tx.send(Progress::Warning(error));
+    }
+}
+
+/// What the mirror ledger files a job's downloads under: the query and whatever else
+/// changes which posts it returns. `None` for jobs mirror mode doesn't apply to.
+fn mirror_query(kind: &JobKind, tags: &str) -> Option<String> {
+    let tags = tags.split_whitespace().collect::<Vec<_>>().join(" ");
+    match kind {
+        JobKind::Favourites { users, all, .. } => Some(format!(
+            "fav:{}{} {tags}",
+            users.join(","),
+            if *all { " (all)" } else { "" }
+        )),
+        JobKind::Tags => Some(tags),
+        JobKind::Union(queries) => Some(format!("any of: {}", queries.join(" | "))),
+        _ => None,
+    }
+}
+
+/// Old files of replaced posts, by post id, with the md5 they were recorded with.
+type Superseded = std::collections::HashMap<u64, (PathBuf, String)>;
+
+/// Fetches every tracked post with a recorded md5 and returns those whose current md5
+/// differs, i.e. the file was replaced on the site after we downloaded it. The recorded
+/// md5 comes from the saved file list (topped up from the manifest), or from the
+/// duplicate index entry for the saved path. With `archive_old`, the old files of those
+/// posts are returned too.
+fn find_replacements(
+    settings: &DownloadSettings,
+    output_dir: &std::path::Path,
+    archive_old: bool,
+    context: &CliContext,
+    client: &reqwest::blocking::Client,
+    login: &Login,
+    tx: &Sender<Progress>,
+) -> Result<(Vec<Post>, Superseded), String> {
+    if settings.manifest_path.trim().is_empty() {
+        return Err(
+            "Set a download manifest first; it records which file each post was saved as."
+                .to_owned(),
+        );
+    }
+    let tracked = crate::import::tracked_ids(&settings.track_file);
+    if tracked.is_empty() {
+        return Err("No tracked posts to check. Set a track file first.".to_owned());
+    }
+
+    let index_path = crate::library::index_path(&settings.duplicate_index, output_dir);
+    let index = e_cli::duplicate::DuplicateIndex::load(&index_path)?;
+    let indexed: std::collections::HashMap<PathBuf, &str> = index
+        .iter()
+        .filter_map(|(md5, entry)| {
+            let file = crate::library::resolve_entry(entry, &index_path)?;
+            Some((crate::library::canonical(&file), md5))
+        })
+        .collect();
+    let mut recorded: std::collections::HashMap<u64, (String, Option<PathBuf>)> =
+        std::collections::HashMap::new();
+    // Jobs from before the saved file list existed are only in the manifest.
+    let mut saved =
+        crate::library::SavedFiles::load(&crate::library::SavedFiles::path(output_dir))?;
+    saved.merge_manifest(std::path::Path::new(settings.manifest_path.trim()))?;
+    for (id, file) in saved.posts {
+        if !tracked.contains(&id) {
+            continue;
+        }
+        let md5 = file.md5.or_else(|| {
+            let path = crate::library::canonical(file.path.as_ref()?);
+            indexed.get(&path).map(|md5| (*md5).to_owned())
+        });
+        // Without the md5 the post was saved with there is nothing to compare against.
+        if let Some(md5) = md5 {
+            recorded.insert(id, (md5, file.path));
+        }
+    }
+    if recorded.is_empty() {
+        return Err("No tracked post has a recorded md5 in the saved file list.".to_owned());
+    }
+
+    let ids: Vec<u64> = recorded.keys().copied().collect();
+    let _ = tx.send(Progress::Status(format!(
+        "Checking {} tracked posts...",
+        ids.len()
+    )));
+    let replaced: Vec<Post> = get_post_data(context, client, login, &ids)
+        .into_iter()
+        .filter(|post| {
+            recorded
+                .get(&post.id)
+                .is_some_and(|(md5, _)| *md5 != post.file.md5)
+        })
+        .collect();
+
+    let mut superseded = Superseded::new();
+    if archive_old {
+        for post in &replaced {
+            if let Some((md5, Some(path))) = recorded.remove(&post.id) {
+                superseded.insert(post.id, (path, md5));
+            }
+        }
+    }
+    Ok((replaced, superseded))
+}
+
+/// Old files moved into `replaced/`, by post id, with the path each came from.
+type Archived = std::collections::HashMap<u64, (PathBuf, PathBuf)>;
+
+/// Moves the old files of replaced posts into `replaced/` before the new files are
+/// downloaded, so a new file saved under the same name can't overwrite them. A file is
+/// only moved while it still has its old md5.
+fn archive_superseded(
+    output_dir: &std::path::Path,
+    superseded: &Superseded,
+    tx: &Sender<Progress>,
+) -> Archived {
+    let archive_dir = output_dir.join("replaced");
+    let mut archived = Archived::new();
+    for (id, (old, md5)) in superseded {
+        let Some(name) = old.file_name() else {
+            continue;
+        };
+        if !crate::library::md5_file(old).is_ok_and(|current| current == *md5) {
+            continue;
+        }
+        // Keep an earlier archived version of the same name.
+        let mut target = archive_dir.join(name);
+        if target.exists() {
+            target = archive_dir.join(format!("{id}-{}", name.to_string_lossy()));
+        }
+        let result =
+            std::fs::create_dir_all(&archive_dir).and_then(|()| std::fs::rename(old, &target));
+        match result {
+            Ok(()) => {
+                archived.insert(*id, (target, old.clone()));
+            }
+            Err(error) => {
+                let _ = tx.send(Progress::Warning(format!(
+                    "Could not move {} into replaced/: {error}",
+                    old.display()
+                )));
+            }
+        }
+    }
+    archived
+}
+
+/// Moves archived files back where they were when their post's new file wasn't
+/// downloaded, so a failed or stopped replacement leaves the old file in place.
+fn restore_unreplaced(
+    archived: &Archived,
+    records: &[e_cli::DownloadRecord],
+    tx: &Sender<Progress>,
+) {
+    let downloaded: std::collections::HashSet<u64> =
+        records.iter().map(|record| record.post_id).collect();
+    for (id, (archive, original)) in archived {
+        if downloaded.contains(id) {
+            continue;
+        }
+        if original.exists() {
+            let _ = tx.send(Progress::Warning(format!(
+                "{} was not replaced but its name is taken; the old file stays in replaced/.",
+                original.display()
+            )));
+            continue;
+        }
+        if let Err(error) = std::fs::rename(archive, original) {
+            let _ = tx.send(Progress::Warning(format!(
+                "Could not move {} back from replaced/: {error}",
+                original.display()
+            )));
+        }
+    }
+}
+
+/// Preview hashes of the posts the near-duplicate check kept, by post id. They go into
+/// the perceptual index only once their files are written, so a failed or cancelled
+/// download doesn't make later jobs skip the post.
+struct PendingHashes {
+    index: crate::perceptual::PerceptualIndex,
+    index_path: PathBuf,
+    hashes: std::collections::HashMap<u64, (String, u64)>,
+}
+
+impl PendingHashes {
+    /// Records the hashes of the posts in `records`.
+    fn record(&mut self, records: &[e_cli::DownloadRecord]) {
+        for record in records {
+            if let Some((md5, hash)) = self.hashes.remove(&record.post_id) {
+                self.index
+                    .entries
+                    .entry(md5)
+                    .or_insert(crate::perceptual::PerceptualEntry { hash, path: None });
+            }
+        }
+    }
+
+    fn save(&self, tx: &Sender<Progress>) {
+        if let Err(error) = self.index.save(&self.index_path) {
+            let _ = tx.send(Progress::Status(error));
+        }
+    }
+}
+
+/// Drops posts whose preview looks like an image already in the perceptual index or like
+/// another post kept earlier in the job. Posts the md5 index already has are left to the
+/// download to skip, and posts hashed before reuse their stored hash instead of fetching
+/// the preview again. Returns how many posts were dropped, each reported as a finished
+/// tick, and the hashes to record once the rest are downloaded.
+#[allow(clippy::too_many_arguments)]
+fn skip_near_duplicates(
+    pages: &mut [Vec<Post>],
+    settings: &DownloadSettings,
+    output_dir: &std::path::Path,
+    client: &reqwest::blocking::Client,
+    pool: &rayon::ThreadPool,
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+) -> (i64, Option<PendingHashes>) {
+    use rayon::prelude::*;
+
+    let index_path = crate::perceptual::index_path(&crate::library::index_path(
+        &settings.duplicate_index,
+        output_dir,
+    ));
+    let index = match crate::perceptual::PerceptualIndex::load(&index_path) {
+        Ok(index) => index,
+        Err(error) => {
+            // An error would end the job in the UI; carry on without the check instead.
+            let _ = tx.send(Progress::Status(format!(
+                "Near-duplicate check skipped: {error}"
+            )));
+            return (0, None);
+        }
+    };
+    let _ = tx.send(Progress::Status(
+        "Checking previews for near-duplicates...".into(),
+    ));
+
+    let indexed = e_cli::duplicate::DuplicateIndex::load(&crate::library::index_path(
+        &settings.duplicate_index,
+        output_dir,
+    ))
+    .unwrap_or_default();
+
+    // Tree items index `md5s`: the indexed images first, then the posts kept so far.
+    let threshold = settings.near_duplicate_threshold;
+    let mut md5s: Vec<String> = index.entries.keys().cloned().collect();
+    let mut tree = crate::perceptual::BkTree::default();
+    for (i, entry) in index.entries.values().enumerate() {
+        tree.insert(entry.hash, i);
+    }
+    let mut hashes = std::collections::HashMap::new();
+    let mut dropped = 0;
+    let mut close = Vec::new();
+    for posts in pages.iter_mut() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let previews: Vec<Option<u64>> = pool.install(|| {
+            posts
+                .par_iter()
+                .map(|post| {
+                    if indexed.contains(&post.file.md5) {
+                        return None;
+                    }
+                    match index.entries.get(&post.file.md5) {
+                        Some(entry) => Some(entry.hash),
+                        None => crate::perceptual::hash_preview(client, &post.file.md5),
+                    }
+                })
+                .collect()
+        });
+        let mut kept = Vec::with_capacity(posts.len());
+        for (post, hash) in posts.drain(..).zip(previews) {
+            let Some(hash) = hash else {
+                kept.push(post);
+                continue;
+            };
+            close.clear();
+            tree.find(hash, threshold, &mut close);
+            if close.iter().any(|&item| md5s[item] != post.file.md5) {
+                dropped += 1;
+                let _ = tx.send(Progress::Tick(0.0));
+                continue;
+            }
+            tree.insert(hash, md5s.len());
+            md5s.push(post.file.md5.clone());
+            hashes.insert(post.id, (post.file.md5.clone(), hash));
+            kept.push(post);
+        }
+        *posts = kept;
+    }
+    (
+        dropped,
+        Some(PendingHashes {
+            index,
+            index_path,
+            hashes,
+        }),
+    )
+}
+
 fn load_duplicate_index(
     configured: &str,
     output_dir: &std::path::Path,
     tx: &Sender<Progress>,
 ) -> Option<Arc<e_cli::duplicate::DuplicateIndex>> {
-    let path = if configured.trim().is_empty() {
-        output_dir.join(".e-cli-md5.json")
-    } else {
-        PathBuf::from(configured)
-    };
+    let path = crate::library::index_path(configured, output_dir);
     match e_cli::duplicate::DuplicateIndex::load(&path) {
         Ok(index) => Some(Arc::new(index)),
         Err(error) => {
@@ -432,11 +1789,12 @@ fn finish_download(
 ) {
     if !settings.dry_run {
         if !settings.manifest_path.trim().is_empty() {
-            if let Err(error) = e_cli::manifest::write(
-                std::path::Path::new(settings.manifest_path.trim()),
-                &statistics,
-            ) {
-                let _ = tx.send(Progress::Error(error));
+            let manifest = std::path::Path::new(settings.manifest_path.trim());
+            match e_cli::manifest::write(manifest, &statistics) {
+                Ok(()) => remember_saved_files(manifest, output_dir, tx),
+                Err(error) => {
+                    let _ = tx.send(Progress::Error(error));
+                }
             }
         }
         let failure_path = if settings.failure_manifest.trim().is_empty() {
@@ -461,6 +1819,22 @@ fn finish_download(
     let _ = tx.send(Progress::Finished(statistics));
 }
 
+/// Adds the manifest a job just wrote to the folder's saved file list.
+fn remember_saved_files(
+    manifest: &std::path::Path,
+    output_dir: &std::path::Path,
+    tx: &Sender<Progress>,
+) {
+    let path = crate::library::SavedFiles::path(output_dir);
+    let result = crate::library::SavedFiles::load(&path).and_then(|mut saved| {
+        saved.merge_manifest(manifest)?;
+        saved.save(&path)
+    });
+    if let Err(error) = result {
+        let _ = tx.send(Progress::Warning(error));
+    }
+}
+
 fn run_retry_failed(
     settings: &DownloadSettings,
     output_dir: &std::path::Path,
@@ -484,11 +1858,7 @@ fn run_retry_failed(
     if !settings.dry_run {
         funcs::ensure_dl_dir(&retry_dir);
     }
-    let duplicate_path = if settings.duplicate_index.trim().is_empty() {
-        retry_dir.join(".e-cli-md5.json")
-    } else {
-        PathBuf::from(settings.duplicate_index.trim())
-    };
+    let duplicate_path = crate::library::index_path(&settings.duplicate_index, &retry_dir);
     let duplicate_index = e_cli::duplicate::DuplicateIndex::load(&duplicate_path)
         .ok()
         .map(Arc::new);
@@ -514,6 +1884,9 @@ fn run_retry_failed(
         .map(|record| record.post_id)
         .collect::<Vec<_>>();
     let posts = get_post_data(&context, &client, &login, &ids);
+    if !preflight_space(posts.iter(), settings, &retry_dir, tx) {
+        return;
+    }
     let _ = tx.send(Progress::Total(ids.len() as u64));
     let stats = funcs::download_with_options(
         &client,
@@ -533,15 +1906,367 @@ fn run_retry_failed(
     finish_download(settings, &retry_dir, &context, stats, tx);
 }
 
+/// Re-downloads broken files one post at a time into a hidden folder beside the file,
+/// bypassing the tracker and duplicate index. Only a download whose md5 matches the post
+/// replaces the file: the old file goes to the trash and the new one takes its path, so a
+/// failed repair leaves the library as it was.
+fn run_repair(
+    context: &CliContext,
+    login: &Login,
+    issues: &[crate::audit::AuditIssue],
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+) {
+    let unresolved: Vec<String> = issues
+        .iter()
+        .filter(|issue| issue.post_id.is_none())
+        .filter_map(|issue| issue.md5.clone())
+        .collect();
+    let by_md5 = if unresolved.is_empty() {
+        Default::default()
+    } else {
+        let _ = tx.send(Progress::Status(format!(
+            "Looking up {} md5s...",
+            unresolved.len()
+        )));
+        match crate::api::resolve_md5s(
+            context.nsfw,
+            login,
+            &unresolved,
+            cancel,
+            &mut crate::api::Throttle::default(),
+        ) {
+            Ok(found) => found,
+            Err(error) => {
+                let _ = tx.send(Progress::Error(error));
+                return;
+            }
+        }
+    };
+    let targets: Vec<(u64, &std::path::Path)> = issues
+        .iter()
+        .filter_map(|issue| {
+            let id = issue
+                .post_id
+                .or_else(|| by_md5.get(issue.md5.as_ref()?).copied())?;
+            Some((id, issue.path.as_path()))
+        })
+        .collect();
+    let unknown = issues.len() - targets.len();
+    if cancel.load(Ordering::Relaxed) {
+        let _ = tx.send(Progress::Cancelled);
+        return;
+    }
+
+    let client = get_client();
+    let _ = tx.send(Progress::Status("Fetching post data...".to_owned()));
+    let ids: Vec<u64> = targets.iter().map(|(id, _)| *id).collect();
+    let posts: std::collections::HashMap<u64, Post> = get_post_data(context, &client, login, &ids)
+        .into_iter()
+        .map(|post| (post.id, post))
+        .collect();
+    let _ = tx.send(Progress::Total(targets.len() as u64));
+
+    let mut repaired = 0;
+    let mut failed = Vec::new();
+    for (id, path) in targets {
+        if cancel.load(Ordering::Relaxed) {
+            let _ = tx.send(Progress::Cancelled);
+            return;
+        }
+        let result = match posts.get(&id) {
+            Some(post) => repair_file(context, &client, login, post, path, cancel),
+            None => Err(format!("post {id} is gone")),
+        };
+        match result {
+            Ok(()) => repaired += 1,
+            Err(error) => failed.push(format!("{}: {error}", path.display())),
+        }
+        let _ = tx.send(Progress::Tick(0.0));
+    }
+
+    let mut summary = format!("Repaired {repaired} of {} files.", issues.len());
+    if unknown > 0 {
+        summary.push_str(&format!(" {unknown} have no known post."));
+    }
+    if !failed.is_empty() {
+        summary.push_str(&format!(
+            " {} failed and were left as they were:\n{}",
+            failed.len(),
+            failed.join("\n")
+        ));
+    }
+    let _ = tx.send(Progress::Summary(summary));
+}
+
+/// Downloads `post` and moves it onto `path` once its md5 checks out.
+fn repair_file(
+    context: &CliContext,
+    client: &reqwest::blocking::Client,
+    login: &Login,
+    post: &Post,
+    path: &std::path::Path,
+    cancel: &Arc<AtomicBool>,
+) -> Result<(), String> {
+    let staging = path
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .join(format!(".e-cli-repair-{}", post.id));
+    funcs::ensure_dl_dir(&staging);
+    let result = (|| {
+        let result = funcs::download_with_options(
+            client,
+            login,
+            vec![post.clone()],
+            None,
+            &false,
+            &staging,
+            None,
+            funcs::DownloadOptions {
+                retries: context.retries,
+                duplicate_index: None,
+                cancel: Some(cancel.clone()),
+            },
+        );
+        if result.amount_finished == 0 {
+            return Err("download failed".to_owned());
+        }
+        let downloaded = std::fs::read_dir(&staging)
+            .map_err(|error| error.to_string())?
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|file| file.is_file())
+            .ok_or("download failed")?;
+        let md5 = crate::library::md5_file(&downloaded).map_err(|error| error.to_string())?;
+        if md5 != post.file.md5 {
+            return Err("the downloaded file doesn't match the post's md5".to_owned());
+        }
+        if path.exists() {
+            trash::delete(path)
+                .map_err(|error| format!("could not trash the old file: {error}"))?;
+        }
+        std::fs::rename(&downloaded, path).map_err(|error| error.to_string())
+    })();
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
 /// Packages `dir` into an archive on its own thread (shells out to `7z`).
+/// With a split, files are taken in reading order and written as `Name v01.cbz`,
+/// `Name v02.cbz`, ... next to `dir`.
 pub fn spawn_zip(
     dir: PathBuf,
     name: String,
     format: ArchiveFormat,
+    split: ArchiveSplit,
     tx: Sender<ZipEvent>,
 ) -> JoinHandle<()> {
     thread::spawn(move || {
-        let ok = e_cli::commands::zip_downloads(&dir, &name, format);
+        let ok = match split {
+            ArchiveSplit::None => e_cli::commands::zip_downloads(&dir, &name, format),
+            split => zip_volumes(&dir, &name, format, split, &tx),
+        };
         let _ = tx.send(ZipEvent::Finished(ok));
     })
 }
+
+fn zip_volumes(
+    dir: &std::path::Path,
+    name: &str,
+    format: ArchiveFormat,
+    split: ArchiveSplit,
+    tx: &Sender<ZipEvent>,
+) -> bool {
+    let files = archive_files(dir);
+    if files.is_empty() {
+        return false;
+    }
+    let volumes = split_volumes(files, split);
+    let width = volumes.len().to_string().len().max(2);
+    let out_dir = dir
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let (archive_type, extension) = match format {
+        ArchiveFormat::Zip => ("zip", "zip"),
+        ArchiveFormat::SevenZip => ("7z", "7z"),
+        ArchiveFormat::Cbz => ("zip", "cbz"),
+    };
+    for (i, volume) in volumes.iter().enumerate() {
+        let _ = tx.send(ZipEvent::Volume {
+            index: i + 1,
+            total: volumes.len(),
+        });
+        let archive = out_dir.join(format!("{name} v{:0width$}.{extension}", i + 1));
+        // 7z runs inside `dir`, so the output path must not be relative to our cwd.
+        let archive = std::path::absolute(&archive).unwrap_or(archive);
+        // `7z a` appends to an existing archive, so start each volume fresh.
+        let _ = std::fs::remove_file(&archive);
+        let status = std::process::Command::new("7z")
+            .current_dir(dir)
+            .arg("a")
+            .arg(format!("-t{archive_type}"))
+            .arg(&archive)
+            .arg("--")
+            .args(volume.iter().map(|(file, _)| file))
+            .stdout(std::process::Stdio::null())
+            .status();
+        if !status.is_ok_and(|status| status.success()) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Lists the media files directly inside `dir` in reading order, together with their sizes.
+/// Pool downloads are prefixed with their position, so names are compared numerically first.
+fn archive_files(dir: &std::path::Path) -> Vec<(std::ffi::OsString, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(std::ffi::OsString, u64)> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| {
+            let size = entry.metadata().map(|meta| meta.len()).unwrap_or(0);
+            (entry.file_name(), size)
+        })
+        .collect();
+    files.sort_by_cached_key(|(file, _)| reading_order_key(&file.to_string_lossy()));
+    files
+}
+
+fn reading_order_key(name: &str) -> (u64, String) {
+    let digits: String = name.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (digits.parse().unwrap_or(u64::MAX), name.to_owned())
+}
+
+fn split_volumes<T>(files: Vec<(T, u64)>, split: ArchiveSplit) -> Vec<Vec<(T, u64)>> {
+    let mut volumes: Vec<Vec<(T, u64)>> = Vec::new();
+    let mut current_size = 0;
+    for (file, size) in files {
+        let start_new = match (volumes.last(), split) {
+            (None, _) => true,
+            (Some(volume), ArchiveSplit::Pages(max)) => volume.len() >= max.max(1),
+            (Some(volume), ArchiveSplit::Size(max)) => {
+                !volume.is_empty() && current_size + size > max
+            }
+            (Some(_), ArchiveSplit::None) => false,
+        };
+        if start_new {
+            volumes.push(Vec::new());
+            current_size = 0;
+        }
+        current_size += size;
+        if let Some(volume) = volumes.last_mut() {
+            volume.push((file, size));
+        }
+    }
+    volumes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A post as `posts.json` returns it, with only the id mattering.
+    fn post(id: u64) -> Post {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "created_at": "2024-01-01T00:00:00.000-05:00",
+            "updated_at": "2024-01-01T00:00:00.000-05:00",
+            "file": {
+                "width": 100,
+                "height": 100,
+                "ext": "png",
+                "size": 1000,
+                "md5": format!("{id:032x}"),
+                "url": null
+            },
+            "preview": { "width": 100, "height": 100, "url": null },
+            "sample": { "has": false, "height": 100, "width": 100, "url": null, "alternates": {} },
+            "score": { "up": 0, "down": 0, "total": 0 },
+            "tags": {
+                "general": [], "artist": [], "contributor": [], "copyright": [],
+                "character": [], "species": [], "invalid": [], "meta": [], "lore": []
+            },
+            "locked_tags": [],
+            "change_seq": 0,
+            "flags": {
+                "pending": false, "flagged": false, "note_locked": false,
+                "status_locked": false, "rating_locked": false, "deleted": false
+            },
+            "rating": "s",
+            "fav_count": 0,
+            "sources": [],
+            "pools": [],
+            "relationships": {
+                "parent_id": null, "has_children": false,
+                "has_active_children": false, "children": []
+            },
+            "approver_id": null,
+            "uploader_id": 1,
+            "description": "",
+            "comment_count": 0,
+            "is_favorited": false,
+            "has_notes": false,
+            "duration": null
+        }))
+        .unwrap()
+    }
+
+    fn pages(ids: &[&[u64]]) -> Vec<Vec<Post>> {
+        ids.iter()
+            .map(|page| page.iter().map(|id| post(*id)).collect())
+            .collect()
+    }
+
+    fn ids(pages: &[Vec<Post>]) -> Vec<Vec<u64>> {
+        pages
+            .iter()
+            .map(|page| page.iter().map(|post| post.id).collect())
+            .collect()
+    }
+
+    #[test]
+    fn merge_favourites_any_keeps_first_occurrence() {
+        let found = vec![
+            ("alice", pages(&[&[5, 4], &[3]])),
+            ("bob", pages(&[&[6, 4, 3], &[2]])),
+        ];
+        let (merged, folders) = merge_favourites(found, false, false);
+        assert_eq!(ids(&merged), [vec![5, 4], vec![3], vec![6], vec![2]]);
+        assert!(folders.is_empty());
+    }
+
+    #[test]
+    fn merge_favourites_per_user_folders() {
+        let found = vec![
+            ("alice", pages(&[&[5, 4]])),
+            ("bob", pages(&[&[5, 4]])),
+            ("carol", pages(&[&[9]])),
+        ];
+        let (merged, folders) = merge_favourites(found, false, true);
+        assert_eq!(ids(&merged), [vec![5, 4], vec![9]]);
+        assert_eq!(
+            folders,
+            [PathBuf::from("fav_alice"), PathBuf::from("fav_carol")]
+        );
+
+        let (_, folders) = merge_favourites(vec![("b/o:b.", pages(&[&[1]]))], false, true);
+        assert_eq!(folders, [PathBuf::from("fav_b_o_b")]);
+    }
+
+    #[test]
+    fn merge_favourites_all_intersects() {
+        let found = vec![
+            ("alice", pages(&[&[9, 7, 5], &[3, 1]])),
+            ("bob", pages(&[&[8, 7], &[3, 2]])),
+            ("carol", pages(&[&[7, 3, 1]])),
+        ];
+        let (merged, folders) = merge_favourites(found, true, true);
+        assert_eq!(ids(&merged), [vec![7], vec![3]]);
+        assert!(folders.is_empty());
+    }
+}
@@ -0,0 +1,527 @@
+//! Selective cleanup of the download folder. A plan is always built and shown first; only
+//! a confirmed plan is moved to the system trash. Every cleanup is remembered in
+//! `cleanup-history.json` next to `gui.toml` so it can be restored from the trash later.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{library, settings};
+
+/// Older cleanups are forgotten once the history grows past this.
+const HISTORY_LIMIT: usize = 50;
+
+/// Extensions left behind by interrupted downloads.
+const PARTIAL_EXTENSIONS: &[&str] = &["part", "partial", "tmp", "temp", "crdownload", "download"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CleanupMode {
+    Everything,
+    OlderThan,
+    Partial,
+    Subfolder,
+    TagRule,
+}
+
+impl CleanupMode {
+    pub const ALL: [Self; 5] = [
+        Self::Everything,
+        Self::OlderThan,
+        Self::Partial,
+        Self::Subfolder,
+        Self::TagRule,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Everything => "Whole folder",
+            Self::OlderThan => "Files older than N days",
+            Self::Partial => "Partial/temporary files",
+            Self::Subfolder => "One job subfolder",
+            Self::TagRule => "Files matching a tag rule (sidecars)",
+        }
+    }
+}
+
+pub struct CleanupOptions {
+    pub mode: CleanupMode,
+    pub days: u64,
+    pub subfolder: String,
+    pub tag_rule: String,
+}
+
+/// What a cleanup would trash: whole folders or single files.
+pub struct CleanupPlan {
+    pub targets: Vec<PathBuf>,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+pub fn plan(dir: &Path, options: &CleanupOptions) -> Result<CleanupPlan, String> {
+    if !dir.exists() {
+        return Err(format!("No {} folder found.", dir.display()));
+    }
+    let targets = match options.mode {
+        // Everything but the hidden state files e-cli keeps in the folder.
+        CleanupMode::Everything => {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+                .map_err(|error| format!("Could not read {}: {error}", dir.display()))?
+                .flatten()
+                .filter(|entry| !is_hidden(&entry.path()))
+                .map(|entry| entry.path())
+                .collect();
+            entries.sort();
+            entries
+        }
+        CleanupMode::Subfolder => {
+            let name = options.subfolder.trim();
+            let folder = dir.join(name);
+            if name.is_empty() || !folder.is_dir() {
+                return Err("Choose a subfolder first.".to_owned());
+            }
+            vec![folder]
+        }
+        CleanupMode::OlderThan => {
+            let cutoff = SystemTime::now()
+                .checked_sub(Duration::from_secs(options.days * 24 * 60 * 60))
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            walk_files(dir)
+                .into_iter()
+                .filter(|file| {
+                    file.metadata()
+                        .and_then(|meta| meta.modified())
+                        .is_ok_and(|modified| modified < cutoff)
+                })
+                .collect()
+        }
+        CleanupMode::Partial => walk_files(dir)
+            .into_iter()
+            .filter(|file| is_partial(file))
+            .collect(),
+        CleanupMode::TagRule => {
+            let rule = TagRule::parse(&options.tag_rule);
+            if rule.is_empty() {
+                return Err("Enter at least one tag for the rule.".to_owned());
+            }
+            walk_files(dir)
+                .into_iter()
+                .filter(|file| !is_sidecar(file))
+                .filter(|file| sidecar_tags(file).is_some_and(|tags| rule.matches(&tags)))
+                .flat_map(|file| {
+                    let mut with_sidecars = sidecars(&file);
+                    with_sidecars.insert(0, file);
+                    with_sidecars
+                })
+                .collect()
+        }
+    };
+    let (files, bytes) = targets.iter().fold((0, 0), |(files, bytes), target| {
+        if target.is_dir() {
+            let inner = walk_files(target);
+            let size: u64 = inner.iter().map(|file| file_size(file)).sum();
+            (files + inner.len(), bytes + size)
+        } else {
+            (files + 1, bytes + file_size(target))
+        }
+    });
+    Ok(CleanupPlan {
+        targets,
+        files,
+        bytes,
+    })
+}
+
+/// Names of the folders directly inside `dir`, e.g. one per pool.
+pub fn subfolders(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Files under `dir`, leaving out hidden files and folders such as e-cli's state files
+/// (`.e-cli-md5.json`, `.e-cli-phash.json`, `.e-cli-failed.json`, `.e-cli-artists.json`).
+fn walk_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(folder) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&folder) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if is_hidden(&entry.path()) {
+                continue;
+            }
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => pending.push(entry.path()),
+                Ok(kind) if kind.is_file() => files.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+    files
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+fn file_size(file: &Path) -> u64 {
+    file.metadata().map(|meta| meta.len()).unwrap_or(0)
+}
+
+fn extension(file: &Path) -> String {
+    file.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn is_partial(file: &Path) -> bool {
+    PARTIAL_EXTENSIONS.contains(&extension(file).as_str()) || file_size(file) == 0
+}
+
+fn is_sidecar(file: &Path) -> bool {
+    matches!(extension(file).as_str(), "json" | "txt")
+}
+
+/// Sidecars sit next to the media file as `<name>.json`/`<name>.txt` or
+/// `<name>.<ext>.json`/`<name>.<ext>.txt`.
+fn sidecars(file: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for ext in ["json", "txt"] {
+        let replaced = file.with_extension(ext);
+        let appended = PathBuf::from(format!("{}.{ext}", file.display()));
+        for candidate in [replaced, appended] {
+            if candidate.is_file() && !found.contains(&candidate) {
+                found.push(candidate);
+            }
+        }
+    }
+    found
+}
+
+/// Tags from the file's sidecar: a post JSON with a `tags` object of category lists (or a
+/// space-separated string), or a text file of whitespace/comma separated tags.
+fn sidecar_tags(file: &Path) -> Option<Vec<String>> {
+    let sidecar = sidecars(file).into_iter().next()?;
+    let text = std::fs::read_to_string(&sidecar).ok()?;
+    if extension(&sidecar) == "txt" {
+        return Some(
+            text.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_ascii_lowercase)
+                .collect(),
+        );
+    }
+    let value: serde_json::Value = serde_json::from_str(&text).ok()?;
+    let tags = value
+        .get("tags")
+        .or_else(|| value.get("post")?.get("tags"))?;
+    let mut found = Vec::new();
+    match tags {
+        serde_json::Value::String(tags) => found.extend(tags.split_whitespace().map(str::to_owned)),
+        serde_json::Value::Object(categories) => {
+            for tag in categories
+                .values()
+                .filter_map(|list| list.as_array())
+                .flatten()
+            {
+                found.extend(tag.as_str().map(str::to_owned));
+            }
+        }
+        serde_json::Value::Array(list) => {
+            found.extend(list.iter().filter_map(|tag| Some(tag.as_str()?.to_owned())));
+        }
+        _ => {}
+    }
+    Some(
+        found
+            .into_iter()
+            .map(|tag| tag.to_ascii_lowercase())
+            .collect(),
+    )
+}
+
+/// `tag` must be present, `-tag` must be absent.
+struct TagRule {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TagRule {
+    fn parse(rule: &str) -> Self {
+        let mut parsed = Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+        };
+        for token in rule.split_whitespace().map(str::to_ascii_lowercase) {
+            match token.strip_prefix('-') {
+                Some(tag) if !tag.is_empty() => parsed.exclude.push(tag.to_owned()),
+                Some(_) => {}
+                None => parsed.include.push(token),
+            }
+        }
+        parsed
+    }
+
+    fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    fn matches(&self, tags: &[String]) -> bool {
+        self.include.iter().all(|tag| tags.contains(tag))
+            && !self.exclude.iter().any(|tag| tags.contains(tag))
+    }
+}
+
+/// One batch of items the app moved to the trash.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CleanupRecord {
+    /// Seconds since the UNIX epoch.
+    pub time: u64,
+    pub description: String,
+    /// Absolute original paths, as the trash records them.
+    pub paths: Vec<PathBuf>,
+    /// The duplicate index the cleanup dropped entries from, and those entries (md5 to
+    /// path as stored), so restored files aren't downloaded again.
+    #[serde(default)]
+    pub index_path: Option<PathBuf>,
+    #[serde(default)]
+    pub index_entries: BTreeMap<String, PathBuf>,
+}
+
+fn history_path() -> Result<PathBuf, String> {
+    settings::path().map(|path| path.with_file_name("cleanup-history.json"))
+}
+
+/// Loads the cleanup history, newest last, treating a missing file as empty.
+pub fn load_history() -> Result<Vec<CleanupRecord>, String> {
+    let path = history_path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|error| format!("Invalid {}: {error}", path.display())),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(format!("Could not read {}: {error}", path.display())),
+    }
+}
+
+pub fn save_history(history: &[CleanupRecord]) -> Result<(), String> {
+    let path = history_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|error| format!("Could not create {}: {error}", parent.display()))?;
+    }
+    let start = history.len().saturating_sub(HISTORY_LIMIT);
+    let text =
+        serde_json::to_string_pretty(&history[start..]).map_err(|error| error.to_string())?;
+    std::fs::write(&path, text)
+        .map_err(|error| format!("Could not write {}: {error}", path.display()))
+}
+
+/// What [`trash_recorded`] did: the targets that could not be trashed, and the history
+/// entry for the rest.
+pub struct Trashed {
+    pub failed: Vec<PathBuf>,
+    pub record: Option<CleanupRecord>,
+}
+
+/// Trashes `targets` through [`library::trash_files`] and describes what actually went
+/// as a history entry. Safe to run off the UI thread; the caller adds the entry to the
+/// history.
+pub fn trash_recorded(
+    targets: &[PathBuf],
+    index_path: &Path,
+    description: String,
+) -> Result<Trashed, String> {
+    // Canonicalize first: afterwards the paths no longer exist.
+    let absolute: Vec<PathBuf> = targets
+        .iter()
+        .map(|target| library::canonical(target))
+        .collect();
+    // Taken before trashing so every item of this cleanup was deleted at or after it.
+    let time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+    let (failed, index_entries) = library::trash_files(targets, index_path)?;
+    let paths: Vec<PathBuf> = targets
+        .iter()
+        .zip(absolute)
+        .filter(|(target, _)| !failed.contains(target))
+        .map(|(_, absolute)| absolute)
+        .collect();
+    let record = (!paths.is_empty()).then(|| CleanupRecord {
+        time,
+        description,
+        paths,
+        index_path: Some(index_path.to_path_buf()),
+        index_entries,
+    });
+    Ok(Trashed { failed, record })
+}
+
+/// Puts a cleanup's items back where they were and re-adds the duplicate index entries
+/// of the restored files. Returns the items that could not be restored, with the reason.
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+pub fn restore(record: &CleanupRecord) -> Result<Vec<(PathBuf, String)>, String> {
+    let items =
+        trash::os_limited::list().map_err(|error| format!("Could not read the trash: {error}"))?;
+    let mut failed = Vec::new();
+    for path in &record.paths {
+        // The same path may have been trashed more than once; take the first copy
+        // trashed since this cleanup started.
+        let item = items
+            .iter()
+            .filter(|item| &item.original_path() == path && item.time_deleted >= record.time as i64)
+            .min_by_key(|item| item.time_deleted);
+        let Some(item) = item else {
+            failed.push((path.clone(), "no longer in the trash".to_owned()));
+            continue;
+        };
+        if path.exists() {
+            failed.push((path.clone(), "something already exists there".to_owned()));
+            continue;
+        }
+        if let Err(error) = trash::os_limited::restore_all([item.clone()]) {
+            failed.push((path.clone(), error.to_string()));
+        }
+    }
+    // Entries of files that exist again, unless the md5 was indexed elsewhere since.
+    if let Some(index_path) = record
+        .index_path
+        .as_deref()
+        .filter(|_| !record.index_entries.is_empty())
+    {
+        let mut index = e_cli::duplicate::DuplicateIndex::load(index_path)?;
+        let before = index.len();
+        for (md5, entry) in &record.index_entries {
+            if !index.contains(md5) && library::resolve_entry(entry, index_path).is_some() {
+                index.insert(md5.clone(), entry.clone());
+            }
+        }
+        if index.len() > before {
+            index.save(index_path)?;
+        }
+    }
+    Ok(failed)
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+pub fn restore(_record: &CleanupRecord) -> Result<Vec<(PathBuf, String)>, String> {
+    Err("Restoring from the trash isn't supported on this platform.".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh folder under the system temp dir, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("e-cli-gui-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn options(mode: CleanupMode) -> CleanupOptions {
+        CleanupOptions {
+            mode,
+            days: 1,
+            subfolder: String::new(),
+            tag_rule: String::new(),
+        }
+    }
+
+    fn sorted(mut targets: Vec<PathBuf>) -> Vec<PathBuf> {
+        targets.sort();
+        targets
+    }
+
+    #[test]
+    fn everything_leaves_state_files() {
+        let dir = TempDir::new("everything");
+        let image = dir.file("a.png", "image");
+        dir.file("pool/1.jpg", "page");
+        dir.file(".e-cli-md5.json", "{}");
+        dir.file(".e-cli-mirror.json", "{}");
+        let plan = plan(&dir.0, &options(CleanupMode::Everything)).unwrap();
+        assert_eq!(plan.targets, [image, dir.0.join("pool")]);
+        assert_eq!(plan.files, 2);
+        assert_eq!(plan.bytes, 9);
+    }
+
+    #[test]
+    fn partial_files_only() {
+        let dir = TempDir::new("partial");
+        dir.file("done.png", "image");
+        let part = dir.file("sub/b.jpg.part", "half");
+        let empty = dir.file("empty.webm", "");
+        dir.file(".e-cli-failed.json", "");
+        dir.file(".hidden/c.tmp", "x");
+        let plan = plan(&dir.0, &options(CleanupMode::Partial)).unwrap();
+        assert_eq!(sorted(plan.targets), sorted(vec![part, empty]));
+        assert_eq!(plan.files, 2);
+    }
+
+    #[test]
+    fn older_than_skips_new_files() {
+        let dir = TempDir::new("older");
+        dir.file("new.png", "image");
+        let plan = plan(&dir.0, &options(CleanupMode::OlderThan)).unwrap();
+        assert!(plan.targets.is_empty());
+    }
+
+    #[test]
+    fn subfolder_and_missing_folder() {
+        let dir = TempDir::new("subfolder");
+        dir.file("pool/1.jpg", "page");
+        let mut chosen = options(CleanupMode::Subfolder);
+        assert!(plan(&dir.0, &chosen).is_err());
+        chosen.subfolder = "pool".to_owned();
+        assert_eq!(plan(&dir.0, &chosen).unwrap().targets, [dir.0.join("pool")]);
+        assert!(plan(&dir.0.join("missing"), &chosen).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+//! Human-readable sizes and durations, safe folder names and CSV line splitting, shared
+//! by the UI and the job threads.
+
+use std::time::Duration;
+
+pub fn format_bytes(bytes: f64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes >= MB {
+        format!("{:.2} MB", bytes / MB)
+    } else if bytes >= 1024.0 {
+        format!("{:.2} KB", bytes / 1024.0)
+    } else {
+        format!("{bytes:.0} bytes")
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, (seconds / 60) % 60)
+    } else if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// `name` as a folder name that is valid on every platform: reserved and control
+/// characters become `_`, and surrounding spaces and trailing dots are dropped. An empty
+/// result becomes `fallback`.
+pub fn folder_name(name: &str, fallback: &str) -> String {
+    let folder: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let folder = folder.trim().trim_end_matches('.');
+    if folder.is_empty() {
+        fallback.to_owned()
+    } else {
+        folder.to_owned()
+    }
+}
+
+/// Splits one CSV line, honouring double-quoted fields.
+pub fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
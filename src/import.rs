@@ -0,0 +1,110 @@
+//! Extracts post references from files dropped onto the window: plain text lists, CSV
+//! exports and e-cli download/failure manifests.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::api::{self, PostRefs};
+use crate::format::csv_fields;
+
+/// Reads post references from `path`, picking a parser from the file extension.
+pub fn post_refs_from_file(path: &Path) -> Result<PostRefs, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| format!("Could not read {}: {error}", path.display()))?;
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "json" => {
+            let value: serde_json::Value = serde_json::from_str(&text)
+                .map_err(|error| format!("Invalid JSON in {}: {error}", path.display()))?;
+            Ok(refs_from_json(&value))
+        }
+        "csv" => Ok(refs_from_csv(&text)),
+        _ => Ok(api::parse_post_refs(&text)),
+    }
+}
+
+/// Manifests keep one record per post with a `post_id`; anything else that looks like a
+/// list of posts carries a plain `id`.
+fn refs_from_json(value: &serde_json::Value) -> PostRefs {
+    let mut ids = Vec::new();
+    collect_json_ids(value, "post_id", &mut ids);
+    if ids.is_empty() {
+        collect_json_ids(value, "id", &mut ids);
+    }
+    let mut refs = PostRefs::default();
+    for id in ids {
+        if !refs.ids.contains(&id) {
+            refs.ids.push(id);
+        }
+    }
+    refs
+}
+
+fn collect_json_ids(value: &serde_json::Value, key: &str, ids: &mut Vec<u64>) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_json_ids(item, key, ids);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for (name, field) in fields {
+                if name == key {
+                    if let Some(id) = field.as_u64() {
+                        ids.push(id);
+                        continue;
+                    }
+                }
+                collect_json_ids(field, key, ids);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Uses the `id`/`post_id`/`md5` column when the header has one, otherwise treats every
+/// cell as a post reference.
+fn refs_from_csv(text: &str) -> PostRefs {
+    let mut lines = text.lines();
+    let header: Vec<String> = csv_fields(lines.next().unwrap_or_default())
+        .iter()
+        .map(|cell| cell.trim().to_ascii_lowercase())
+        .collect();
+    let column = header
+        .iter()
+        .position(|name| name == "id" || name == "post_id")
+        .or_else(|| header.iter().position(|name| name == "md5"));
+    let cells: Vec<String> = match column {
+        Some(column) => lines
+            .filter_map(|line| csv_fields(line).into_iter().nth(column))
+            .collect(),
+        None => text.lines().flat_map(csv_fields).collect(),
+    };
+    let list = cells
+        .iter()
+        .map(|cell| cell.trim())
+        .collect::<Vec<_>>()
+        .join("\n");
+    api::parse_post_refs(&list)
+}
+
+/// Post ids recorded in a tracking file (one post per line).
+pub fn tracked_ids(track_file: &str) -> HashSet<u64> {
+    if track_file.trim().is_empty() {
+        return HashSet::new();
+    }
+    std::fs::read_to_string(track_file.trim())
+        .map(|text| {
+            text.lines()
+                .filter_map(|line| {
+                    line.split(|c: char| c.is_whitespace() || c == ',')
+                        .next()
+                        .and_then(|id| id.parse().ok())
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,560 @@
+//! Maintenance for the MD5 duplicate index (`.e-cli-md5.json`) that e-cli consults before
+//! downloading. Long-running work runs on its own thread and reports over the same
+//! `Progress` channel as downloads, so the UI shows it like any other job.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use e_cli::duplicate::DuplicateIndex;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Progress;
+use crate::perceptual;
+
+const INDEX_FILE: &str = ".e-cli-md5.json";
+const MIRROR_FILE: &str = ".e-cli-mirror.json";
+const SAVED_FILE: &str = ".e-cli-saved.json";
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "avif", "webm", "mp4", "swf",
+];
+
+/// Where the duplicate index lives: the configured path, or the download folder default.
+pub fn index_path(configured: &str, output_dir: &Path) -> PathBuf {
+    if configured.trim().is_empty() {
+        output_dir.join(INDEX_FILE)
+    } else {
+        PathBuf::from(configured.trim())
+    }
+}
+
+/// Resolves an indexed path, which may be relative to the working directory or to the
+/// folder holding the index.
+pub fn resolve_entry(entry: &Path, index_path: &Path) -> Option<PathBuf> {
+    if entry.exists() {
+        return Some(entry.to_path_buf());
+    }
+    let beside_index = index_path.parent()?.join(entry);
+    (entry.is_relative() && beside_index.exists()).then_some(beside_index)
+}
+
+pub fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Which md5s each mirrored query downloaded into a folder, kept there as
+/// `.e-cli-mirror.json`. Mirror mode only offers files whose md5 its own query recorded
+/// and no other query did, so jobs sharing a download folder leave each other alone.
+#[derive(Default, Serialize, Deserialize)]
+pub struct MirrorLedger {
+    /// Query description to the md5s it downloaded.
+    pub queries: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl MirrorLedger {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(MIRROR_FILE)
+    }
+
+    /// Loads a ledger, treating a missing file as empty.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|error| format!("Invalid mirror ledger {}: {error}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(format!("Could not read {}: {error}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, text)
+            .map_err(|error| format!("Could not write {}: {error}", path.display()))
+    }
+
+    /// Md5s `query` recorded that are neither in `keep` nor recorded by another query.
+    pub fn stale(&self, query: &str, keep: &HashSet<String>) -> HashSet<String> {
+        let Some(ours) = self.queries.get(query) else {
+            return HashSet::new();
+        };
+        let others: HashSet<&String> = self
+            .queries
+            .iter()
+            .filter(|(other, _)| *other != query)
+            .flat_map(|(_, md5s)| md5s)
+            .collect();
+        ours.iter()
+            .filter(|md5| !keep.contains(*md5) && !others.contains(md5))
+            .cloned()
+            .collect()
+    }
+}
+
+/// The file and md5 each post was last saved with, gathered from the download manifest
+/// after every job and kept as `.e-cli-saved.json`. The manifest only describes the last
+/// job, so replacement checks read this instead.
+#[derive(Default, Serialize, Deserialize)]
+pub struct SavedFiles {
+    pub posts: BTreeMap<u64, SavedFile>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedFile {
+    pub path: Option<PathBuf>,
+    pub md5: Option<String>,
+}
+
+impl SavedFiles {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(SAVED_FILE)
+    }
+
+    /// Loads the list, treating a missing file as empty.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|error| format!("Invalid saved file list {}: {error}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(format!("Could not read {}: {error}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, text)
+            .map_err(|error| format!("Could not write {}: {error}", path.display()))
+    }
+
+    /// Takes over the posts of a download manifest; newer records replace older ones.
+    pub fn merge_manifest(&mut self, manifest: &Path) -> Result<(), String> {
+        for record in crate::audit::manifest_records(manifest)? {
+            let Some(id) = record.post_id else {
+                continue;
+            };
+            self.posts.insert(
+                id,
+                SavedFile {
+                    path: record.path,
+                    md5: record.md5,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Media files with one of the `stale` md5s, from the duplicate index and directly
+/// inside `dirs`. The md5 of a file in `dirs` comes from the duplicate index when it
+/// knows the file, then from an md5 file name, then from hashing.
+pub fn stale_files(dirs: &[PathBuf], index_path: &Path, stale: &HashSet<String>) -> Vec<PathBuf> {
+    let index = DuplicateIndex::load(index_path).unwrap_or_default();
+    let known: HashMap<PathBuf, &str> = index
+        .iter()
+        .filter_map(|(md5, entry)| Some((canonical(&resolve_entry(entry, index_path)?), md5)))
+        .collect();
+    let mut files: Vec<PathBuf> = known
+        .iter()
+        .filter(|(_, md5)| stale.contains(**md5))
+        .map(|(file, _)| file.clone())
+        .collect();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if !path.is_file() || !is_media(&path) {
+                continue;
+            }
+            let path = canonical(&path);
+            if known.contains_key(&path) {
+                continue;
+            }
+            let md5 = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+                .filter(|stem| stem.len() == 32 && stem.chars().all(|c| c.is_ascii_hexdigit()))
+                .or_else(|| md5_file(&path).ok());
+            if md5.is_some_and(|md5| stale.contains(&md5)) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Moves `files` (or folders) to the trash and drops the duplicate index entries of
+/// everything trashed, so the posts are downloaded again if they come back. Returns the
+/// items that could not be trashed and the dropped entries, md5 to path as stored.
+pub fn trash_files(
+    files: &[PathBuf],
+    index_path: &Path,
+) -> Result<(Vec<PathBuf>, BTreeMap<String, PathBuf>), String> {
+    let mut index = DuplicateIndex::load(index_path)?;
+    // Match entries up front: once a file is trashed its path can't be canonicalized.
+    let indexed: Vec<(PathBuf, String, PathBuf)> = index
+        .iter()
+        .filter_map(|(md5, entry)| {
+            Some((
+                canonical(&resolve_entry(entry, index_path)?),
+                md5.to_owned(),
+                entry.to_path_buf(),
+            ))
+        })
+        .collect();
+    let mut failed = Vec::new();
+    let mut forgotten = BTreeMap::new();
+    for file in files {
+        // A folder takes every indexed file under it along.
+        let target = canonical(file);
+        let covered: Vec<&(PathBuf, String, PathBuf)> = indexed
+            .iter()
+            .filter(|(path, _, _)| path.starts_with(&target))
+            .collect();
+        if trash::delete(file).is_err() {
+            failed.push(file.clone());
+            continue;
+        }
+        for (_, md5, entry) in covered {
+            index.remove(md5);
+            forgotten.insert(md5.clone(), entry.clone());
+        }
+    }
+    if !forgotten.is_empty() {
+        index.save(index_path)?;
+    }
+    Ok((failed, forgotten))
+}
+
+pub struct IndexStats {
+    pub entries: usize,
+    pub index_bytes: u64,
+    pub indexed_bytes: u64,
+    pub missing: usize,
+}
+
+pub fn stats(index_path: &Path) -> Result<IndexStats, String> {
+    let index = DuplicateIndex::load(index_path)?;
+    let mut indexed_bytes = 0;
+    let mut missing = 0;
+    for (_, entry) in index.iter() {
+        match resolve_entry(entry, index_path).and_then(|file| file.metadata().ok()) {
+            Some(meta) => indexed_bytes += meta.len(),
+            None => missing += 1,
+        }
+    }
+    Ok(IndexStats {
+        entries: index.len(),
+        index_bytes: std::fs::metadata(index_path)
+            .map(|meta| meta.len())
+            .unwrap_or(0),
+        indexed_bytes,
+        missing,
+    })
+}
+
+pub enum IndexTask {
+    /// Replace the index with hashes of every media file under these folders.
+    Rebuild(Vec<PathBuf>),
+    /// Add hashes of every media file under these folders, e.g. a library downloaded by
+    /// other tools, so those posts are skipped from now on.
+    Import(Vec<PathBuf>),
+    /// Drop entries whose files no longer exist.
+    Prune,
+    /// Add entries from another machine's index, keeping ours when both have a file.
+    Merge(PathBuf),
+    /// Hash the images under these folders into the perceptual index.
+    BuildPerceptual(Vec<PathBuf>),
+    /// Group indexed files that are within this many bits of each other.
+    SimilarReport(u32),
+}
+
+/// Runs an index maintenance task on its own thread. Reports `Total`/`Tick` while hashing
+/// and ends with `Summary`, `Cancelled` or `Error`.
+pub fn spawn_index_task(
+    task: IndexTask,
+    index_path: PathBuf,
+    threads: usize,
+    cancel: Arc<AtomicBool>,
+    tx: Sender<Progress>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let result = match task {
+            IndexTask::Rebuild(folders) => rebuild(&folders, &index_path, threads, &cancel, &tx),
+            IndexTask::Import(folders) => import(&folders, &index_path, threads, &cancel, &tx),
+            IndexTask::Prune => prune(&index_path),
+            IndexTask::Merge(other) => merge(&index_path, &other),
+            IndexTask::BuildPerceptual(folders) => {
+                build_perceptual(&folders, &index_path, threads, &cancel, &tx)
+            }
+            IndexTask::SimilarReport(threshold) => {
+                similar_report(&index_path, threshold, &cancel, &tx)
+            }
+        };
+        let _ = tx.send(match result {
+            Ok(Some(summary)) => Progress::Summary(summary),
+            Ok(None) => Progress::Cancelled,
+            Err(error) => Progress::Error(error),
+        });
+    })
+}
+
+fn rebuild(
+    folders: &[PathBuf],
+    index_path: &Path,
+    threads: usize,
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+) -> Result<Option<String>, String> {
+    let previous = DuplicateIndex::load(index_path)?.len();
+    let Some(hashed) = hash_folders(folders, threads, cancel, tx) else {
+        return Ok(None);
+    };
+    let mut index = DuplicateIndex::default();
+    for (md5, file) in hashed {
+        index.insert(md5, file);
+    }
+    index.save(index_path)?;
+    Ok(Some(format!(
+        "Duplicate index rebuilt: {} entries (was {previous}).",
+        index.len()
+    )))
+}
+
+fn import(
+    folders: &[PathBuf],
+    index_path: &Path,
+    threads: usize,
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+) -> Result<Option<String>, String> {
+    let mut index = DuplicateIndex::load(index_path)?;
+    let Some(hashed) = hash_folders(folders, threads, cancel, tx) else {
+        return Ok(None);
+    };
+    let scanned = hashed.len();
+    let mut added = 0;
+    for (md5, file) in hashed {
+        if !index.contains(&md5) {
+            index.insert(md5, file);
+            added += 1;
+        }
+    }
+    index.save(index_path)?;
+    Ok(Some(format!(
+        "Imported {scanned} files: {added} new index entries, {} total.",
+        index.len()
+    )))
+}
+
+fn prune(index_path: &Path) -> Result<Option<String>, String> {
+    let mut index = DuplicateIndex::load(index_path)?;
+    let before = index.len();
+    index.retain(|_, entry| resolve_entry(entry, index_path).is_some());
+    let removed = before - index.len();
+    if removed > 0 {
+        index.save(index_path)?;
+    }
+    Ok(Some(format!(
+        "Pruned {removed} missing entries, {} left.",
+        index.len()
+    )))
+}
+
+fn merge(index_path: &Path, other_path: &Path) -> Result<Option<String>, String> {
+    let mut index = DuplicateIndex::load(index_path)?;
+    let other = DuplicateIndex::load(other_path)?;
+    let mut added = 0;
+    for (md5, entry) in other.iter() {
+        let ours_present = index
+            .get(md5)
+            .is_some_and(|ours| resolve_entry(ours, index_path).is_some());
+        if !ours_present {
+            index.insert(md5.to_owned(), entry.to_path_buf());
+            added += 1;
+        }
+    }
+    index.save(index_path)?;
+    Ok(Some(format!(
+        "Merged {added} entries from {}, {} total.",
+        other_path.display(),
+        index.len()
+    )))
+}
+
+fn build_perceptual(
+    folders: &[PathBuf],
+    index_path: &Path,
+    threads: usize,
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+) -> Result<Option<String>, String> {
+    use rayon::prelude::*;
+
+    let perceptual_path = perceptual::index_path(index_path);
+    let mut index = perceptual::PerceptualIndex::load(&perceptual_path)?;
+    let _ = tx.send(Progress::Status("Scanning folders...".to_owned()));
+    let mut files = Vec::new();
+    for folder in folders {
+        collect_media_files(folder, &mut files);
+    }
+    files.retain(|file| is_image(file));
+    let _ = tx.send(Progress::Total(files.len() as u64));
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .build()
+        .expect("Error building thread pool");
+    let hashed: Vec<(String, u64, PathBuf)> = pool.install(|| {
+        files
+            .into_par_iter()
+            .filter_map(|file| {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+                let result = md5_file(&file)
+                    .ok()
+                    .zip(perceptual::hash_file(&file))
+                    .map(|(md5, hash)| (md5, hash, file));
+                let _ = tx.send(Progress::Tick(size as f64));
+                result
+            })
+            .collect()
+    });
+    if cancel.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+    let hashed_count = hashed.len();
+    for (md5, hash, file) in hashed {
+        index.entries.insert(
+            md5,
+            perceptual::PerceptualEntry {
+                hash,
+                path: Some(file),
+            },
+        );
+    }
+    index.save(&perceptual_path)?;
+    Ok(Some(format!(
+        "Hashed {hashed_count} images, perceptual index has {} entries.",
+        index.entries.len()
+    )))
+}
+
+fn similar_report(
+    index_path: &Path,
+    threshold: u32,
+    cancel: &AtomicBool,
+    tx: &Sender<Progress>,
+) -> Result<Option<String>, String> {
+    let _ = tx.send(Progress::Status("Comparing images...".to_owned()));
+    let mut index = perceptual::PerceptualIndex::load(&perceptual::index_path(index_path))?;
+    // Deleted files would otherwise link groups that have nothing else in common.
+    index.entries.retain(|_, entry| {
+        entry
+            .path
+            .as_ref()
+            .is_some_and(|file| resolve_entry(file, index_path).is_some())
+    });
+    let Some(groups) = perceptual::similar_groups(&index, threshold, cancel) else {
+        return Ok(None);
+    };
+    let summary = format!("Found {} groups of similar images.", groups.len());
+    let _ = tx.send(Progress::SimilarGroups(groups));
+    Ok(Some(summary))
+}
+
+/// Hashes every media file under `folders` in parallel. Returns `None` when cancelled.
+pub fn hash_folders(
+    folders: &[PathBuf],
+    threads: usize,
+    cancel: &Arc<AtomicBool>,
+    tx: &Sender<Progress>,
+) -> Option<BTreeMap<String, PathBuf>> {
+    use rayon::prelude::*;
+
+    let _ = tx.send(Progress::Status("Scanning folders...".to_owned()));
+    let mut files = Vec::new();
+    for folder in folders {
+        collect_media_files(folder, &mut files);
+    }
+    let _ = tx.send(Progress::Total(files.len() as u64));
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .build()
+        .expect("Error building thread pool");
+    let hashed: Vec<(String, PathBuf)> = pool.install(|| {
+        files
+            .into_par_iter()
+            .filter_map(|file| {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let result = md5_file(&file);
+                let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+                let _ = tx.send(Progress::Tick(size as f64));
+                result.ok().map(|md5| (md5, file))
+            })
+            .collect()
+    });
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+    Some(hashed.into_iter().collect())
+}
+
+/// Recursively lists media files, skipping hidden files and folders.
+pub fn collect_media_files(folder: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => collect_media_files(&path, files),
+            Ok(kind) if kind.is_file() && is_media(&path) => files.push(path),
+            _ => {}
+        }
+    }
+}
+
+/// Files the `image` crate can decode; videos and flash are left out of perceptual hashing.
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|ext| matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "gif" | "webp"))
+}
+
+fn is_media(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|ext| MEDIA_EXTENSIONS.contains(&ext.as_str()))
+}
+
+pub fn md5_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Md5::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
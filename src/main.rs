@@ -1,6 +1,18 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod api;
+mod audit;
 mod backend;
+mod cleanup;
+mod format;
+mod import;
+mod library;
+mod perceptual;
+mod popular;
+mod query;
+mod settings;
+mod tagdb;
+mod watchlist;
 
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -8,13 +20,15 @@ use std::sync::mpsc::Receiver;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use backend::{DownloadSettings, JobKind, Progress, ZipEvent};
+use api::PoolSummary;
+use backend::{ArchiveSplit, DownloadSettings, JobKind, Progress, Quota, ZipEvent};
 use e_cli::cli::ArchiveFormat;
 use e_cli::config as econfig;
 use e_cli::update;
 use eframe::egui;
 use egui::{Align2, Color32, RichText};
 use egui_toast::{Toast, ToastKind, ToastOptions, Toasts};
+use format::{format_bytes, format_duration};
 
 const DL_DIR: &str = "./dl";
 const KEY_FILE: &str = "./key";
@@ -34,6 +48,7 @@ fn main() -> eframe::Result<()> {
             egui_extras_setup(&cc.egui_ctx);
             let mut app = App::default();
             app.load_settings();
+            app.spawn_tag_db_load(cc.egui_ctx.clone(), Vec::new());
             app.spawn_version_check(cc.egui_ctx.clone());
             Ok(Box::new(app))
         }),
@@ -49,6 +64,10 @@ enum Tab {
     Favourites,
     Tags,
     Pool,
+    Set,
+    Posts,
+    Popular,
+    Artists,
     Utilities,
     Config,
 }
@@ -58,6 +77,7 @@ enum PresetSource {
     Tags,
     Favourites,
     Pool,
+    Set,
 }
 
 impl PresetSource {
@@ -66,6 +86,7 @@ impl PresetSource {
             Self::Tags => "Tags",
             Self::Favourites => "Favourites",
             Self::Pool => "Pool",
+            Self::Set => "Set",
         }
     }
 }
@@ -81,10 +102,95 @@ struct ActiveJob {
     status: String,
     downloaded_bytes: f64,
     started_at: Instant,
+    /// The quota that ended the job early, if any.
+    limit_reached: Option<String>,
+}
+
+/// Autocomplete state shared by the tag fields.
+#[derive(Default)]
+struct TagAssist {
+    db: tagdb::TagDb,
+    live_lookup: bool,
+    /// Site suggestions for the last prefix looked up.
+    live: Option<(String, Vec<tagdb::Suggestion>)>,
+    live_rx: Option<Receiver<(String, Vec<tagdb::Suggestion>)>>,
+    /// The last word suggested for, so the list isn't rebuilt every frame. Cleared when
+    /// the database or the site suggestions change.
+    shown: Option<(String, Vec<tagdb::Suggestion>)>,
+}
+
+impl TagAssist {
+    const LIMIT: usize = 8;
+
+    fn suggest(&mut self, prefix: &str) -> &[tagdb::Suggestion] {
+        if self
+            .shown
+            .as_ref()
+            .is_none_or(|(shown, _)| shown.as_str() != prefix)
+        {
+            let found = self.lookup(prefix);
+            self.shown = Some((prefix.to_owned(), found));
+        }
+        self.shown.as_ref().map_or(&[], |(_, found)| found)
+    }
+
+    fn lookup(&self, prefix: &str) -> Vec<tagdb::Suggestion> {
+        let mut found = self.db.suggest(prefix, Self::LIMIT);
+        if let Some((_, live)) = self
+            .live
+            .as_ref()
+            .filter(|(looked_up, _)| prefix.starts_with(looked_up.as_str()))
+        {
+            for suggestion in live {
+                let typed = suggestion.alias.as_ref().unwrap_or(&suggestion.tag.name);
+                if typed.starts_with(prefix)
+                    && !found
+                        .iter()
+                        .any(|known| known.tag.name == suggestion.tag.name)
+                {
+                    found.push(suggestion.clone());
+                }
+            }
+            found.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.tag.post_count));
+            found.truncate(Self::LIMIT);
+        }
+        found
+    }
+}
+
+struct ImportPreview {
+    source: String,
+    refs: api::PostRefs,
+    tracked: usize,
 }
 
 struct ActiveZip {
     rx: Receiver<ZipEvent>,
+    status: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SplitMode {
+    None,
+    Pages,
+    Size,
+}
+
+impl SplitMode {
+    fn label(self) -> &'static str {
+        match self {
+            Self::None => "Single archive",
+            Self::Pages => "By page count",
+            Self::Size => "By size (MB)",
+        }
+    }
+}
+
+/// Which window started the trash run in `App::trash_rx`.
+#[derive(Clone, Copy)]
+enum TrashOrigin {
+    Mirror,
+    Cleanup,
 }
 
 struct App {
@@ -95,10 +201,19 @@ struct App {
     api_key: String,
     fav_tags: String,
     fav_count: u32,
-    fav_random: bool,
+    fav_order: Option<String>,
+    fav_mirror: bool,
+    /// Usernames whose favourites to download; blank means `username`.
+    fav_users: String,
+    /// Only posts all of `fav_users` favourited.
+    fav_all: bool,
+    fav_per_user: bool,
     search_tags: String,
     search_count: u32,
-    search_random: bool,
+    search_order: Option<String>,
+    search_mirror: bool,
+    /// One query per line for a union job.
+    union_queries: String,
     pages: i64,
     threads: usize,
     lower_quality: bool,
@@ -110,14 +225,79 @@ struct App {
     manifest_path: String,
     duplicate_index: String,
     failure_manifest: String,
+    skip_near_duplicates: bool,
+    near_duplicate_threshold: u32,
+    refuse_low_space: bool,
+    min_free_mb: u64,
+    quota_max_mb: u64,
+    quota_max_files: u64,
+    quota_max_minutes: u64,
+    /// Post id searches start next to; 0 starts at the newest post.
+    start_id: u64,
+    /// Whether the search continues with newer posts than `start_id` instead of older.
+    start_newer: bool,
     preset_name: String,
     preset_source: PresetSource,
 
     pool_id: String,
+    pool_search_name: String,
+    pool_search_creator: String,
+    pool_search_rx: Option<Receiver<Result<Vec<PoolSummary>, String>>>,
+    watchlist_new: String,
+    artist_status: Vec<watchlist::ArtistStatus>,
+    artist_rx: Option<Receiver<Vec<watchlist::ArtistStatus>>>,
+    pool_results: Vec<(PoolSummary, bool)>,
+    set_id: String,
+    set_ordered: bool,
+    post_list: String,
+    popular_scale: popular::Scale,
+    popular_from: String,
+    popular_to: String,
+    popular_filter: String,
+    /// The listings the scale and range fields pick, updated when they change.
+    popular_dates: Result<Vec<String>, String>,
+    import_path: String,
+    import_preview: Option<ImportPreview>,
+    index_folders: String,
+    index_merge_path: String,
+    index_stats: Option<String>,
+    similar_groups: Vec<Vec<PathBuf>>,
+    audit_issues: Vec<audit::AuditIssue>,
+    archive_replaced: bool,
+    mirror_stale: Option<Vec<PathBuf>>,
+    /// Total size of `mirror_stale`, summed once when the report arrives.
+    mirror_bytes: u64,
+    mirror_confirm: bool,
+    /// Items being moved to the trash on a worker thread, and where that was started.
+    trash_rx: Option<(TrashOrigin, Receiver<Result<cleanup::Trashed, String>>)>,
+    cleanup_open: bool,
+    cleanup_mode: cleanup::CleanupMode,
+    cleanup_days: u64,
+    cleanup_subfolder: String,
+    /// Folders offered in Subfolder mode, listed when the mode is picked.
+    cleanup_subfolders: Vec<String>,
+    cleanup_tag_rule: String,
+    cleanup_plan: Option<cleanup::CleanupPlan>,
+    /// A preview being built; walking a large folder would stall the window.
+    cleanup_plan_rx: Option<Receiver<Result<cleanup::CleanupPlan, String>>>,
+    cleanup_confirm: bool,
+    cleanup_history: Vec<cleanup::CleanupRecord>,
+    restore_failures: Vec<(PathBuf, String)>,
+    tag_assist: TagAssist,
+    tag_db_rx: Option<Receiver<Result<(tagdb::TagDb, String), String>>>,
+    tag_dump_paths: String,
+    resolve_aliases: bool,
+    drop_implied: bool,
+    relations_rx: Option<Receiver<Result<api::TagRelations, String>>>,
+    query_builder: query::QueryParts,
     zip_name: String,
     zip_format: ArchiveFormat,
+    zip_split: SplitMode,
+    zip_split_pages: usize,
+    zip_split_mb: u64,
 
     config: econfig::Config,
+    gui: settings::GuiSettings,
 
     job: Option<ActiveJob>,
     zip_job: Option<ActiveZip>,
@@ -137,10 +317,16 @@ impl Default for App {
             api_key: String::new(),
             fav_tags: String::new(),
             fav_count: 75,
-            fav_random: false,
+            fav_order: None,
+            fav_mirror: false,
+            fav_users: String::new(),
+            fav_all: false,
+            fav_per_user: false,
             search_tags: String::new(),
             search_count: 75,
-            search_random: false,
+            search_order: None,
+            search_mirror: false,
+            union_queries: String::new(),
             pages: -1,
             threads: 5,
             lower_quality: false,
@@ -152,12 +338,70 @@ impl Default for App {
             manifest_path: String::new(),
             duplicate_index: String::new(),
             failure_manifest: String::new(),
+            skip_near_duplicates: false,
+            near_duplicate_threshold: 6,
+            refuse_low_space: false,
+            min_free_mb: 1024,
+            quota_max_mb: 0,
+            quota_max_files: 0,
+            quota_max_minutes: 0,
+            start_id: 0,
+            start_newer: false,
             preset_name: String::new(),
             preset_source: PresetSource::Tags,
             pool_id: String::new(),
+            pool_search_name: String::new(),
+            pool_search_creator: String::new(),
+            pool_search_rx: None,
+            watchlist_new: String::new(),
+            artist_status: Vec::new(),
+            artist_rx: None,
+            pool_results: Vec::new(),
+            set_id: String::new(),
+            set_ordered: true,
+            post_list: String::new(),
+            popular_scale: popular::Scale::Day,
+            popular_from: popular::today(),
+            popular_to: String::new(),
+            popular_filter: String::new(),
+            popular_dates: popular::dates(&popular::today(), "", popular::Scale::Day),
+            import_path: String::new(),
+            import_preview: None,
+            index_folders: String::new(),
+            index_merge_path: String::new(),
+            index_stats: None,
+            similar_groups: Vec::new(),
+            audit_issues: Vec::new(),
+            archive_replaced: true,
+            mirror_stale: None,
+            mirror_bytes: 0,
+            mirror_confirm: false,
+            trash_rx: None,
+            cleanup_open: false,
+            cleanup_mode: cleanup::CleanupMode::Partial,
+            cleanup_days: 30,
+            cleanup_subfolder: String::new(),
+            cleanup_subfolders: Vec::new(),
+            cleanup_tag_rule: String::new(),
+            cleanup_plan: None,
+            cleanup_plan_rx: None,
+            cleanup_confirm: false,
+            cleanup_history: Vec::new(),
+            restore_failures: Vec::new(),
+            tag_assist: TagAssist::default(),
+            tag_db_rx: None,
+            tag_dump_paths: String::new(),
+            resolve_aliases: true,
+            drop_implied: false,
+            relations_rx: None,
+            query_builder: query::QueryParts::default(),
             zip_name: String::new(),
             zip_format: ArchiveFormat::Cbz,
+            zip_split: SplitMode::None,
+            zip_split_pages: 100,
+            zip_split_mb: 500,
             config: econfig::Config::default(),
+            gui: settings::GuiSettings::default(),
             job: None,
             zip_job: None,
             version_check_rx: None,
@@ -169,19 +413,56 @@ impl Default for App {
 
 impl App {
     fn start_job(&mut self, kind: JobKind, label: &'static str) {
+        let settings = self.download_settings(&kind);
+        self.start_job_with(kind, label, settings);
+    }
+
+    fn start_job_with(&mut self, kind: JobKind, label: &'static str, settings: DownloadSettings) {
         let (tx, rx) = std::sync::mpsc::channel();
         let cancel = Arc::new(AtomicBool::new(false));
-        let (tags, count, random) = match &kind {
-            JobKind::Favourites => (self.fav_tags.clone(), self.fav_count, self.fav_random),
-            JobKind::Tags => (
+        let dry_run = settings.dry_run;
+        backend::spawn_download(
+            kind,
+            settings,
+            PathBuf::from(&self.dl_dir),
+            cancel.clone(),
+            tx,
+        );
+        self.track_job(label, cancel, rx, dry_run);
+        self.toast(format!("Starting {label} download..."), ToastKind::Info);
+    }
+
+    fn download_settings(&self, kind: &JobKind) -> DownloadSettings {
+        let (tags, count, order, mirror) = match kind {
+            JobKind::Favourites { .. } => (
+                self.fav_tags.clone(),
+                self.fav_count,
+                self.fav_order.clone(),
+                self.fav_mirror,
+            ),
+            JobKind::Tags | JobKind::Union(_) => (
                 self.search_tags.clone(),
                 self.search_count,
-                self.search_random,
+                self.search_order.clone(),
+                self.search_mirror,
             ),
-            JobKind::Pool(_) => (String::new(), 0, false),
-            JobKind::RetryFailed => (String::new(), 0, false),
+            // Artist searches have no tab of their own; fetch as much per request as allowed.
+            JobKind::Artists(_) => (String::new(), 250, None, false),
+            JobKind::Pool(_)
+            | JobKind::Pools(_)
+            | JobKind::Set { .. }
+            | JobKind::Posts { .. }
+            | JobKind::Popular { .. } => (String::new(), 0, None, false),
+            JobKind::CheckReplacements { .. } | JobKind::Repair(_) | JobKind::RetryFailed => {
+                (String::new(), 0, None, false)
+            }
+        };
+        let tags = if self.resolve_aliases {
+            query::resolve(&tags, &self.tag_assist.db, self.drop_implied).query
+        } else {
+            tags
         };
-        let settings = DownloadSettings {
+        DownloadSettings {
             nsfw: self.nsfw,
             username: self.username.clone(),
             api_key: self.api_key.clone(),
@@ -189,7 +470,7 @@ impl App {
             count,
             pages: self.pages,
             threads: self.threads,
-            random,
+            order,
             lower_quality: self.lower_quality,
             track_file: self.track_file.clone(),
             retries: self.retries,
@@ -197,14 +478,37 @@ impl App {
             dry_run: self.dry_run,
             manifest_path: self.manifest_path.clone(),
             failure_manifest: self.failure_manifest.clone(),
-        };
-        backend::spawn_download(
-            kind,
-            settings,
-            PathBuf::from(&self.dl_dir),
-            cancel.clone(),
-            tx,
-        );
+            skip_near_duplicates: self.skip_near_duplicates,
+            near_duplicate_threshold: self.near_duplicate_threshold,
+            ignore_tracker: false,
+            mirror,
+            refuse_low_space: self.refuse_low_space,
+            min_free_space: self.min_free_mb.saturating_mul(1024 * 1024),
+            quota: Quota {
+                max_bytes: (self.quota_max_mb > 0)
+                    .then_some(self.quota_max_mb.saturating_mul(1024 * 1024)),
+                max_files: (self.quota_max_files > 0).then_some(self.quota_max_files),
+                max_duration: (self.quota_max_minutes > 0)
+                    .then(|| Duration::from_secs(self.quota_max_minutes.saturating_mul(60))),
+            },
+            // Artist jobs start after each artist's newest fetched post instead.
+            start_from: (self.start_id > 0 && !matches!(kind, JobKind::Artists(_))).then_some(
+                if self.start_newer {
+                    api::Cursor::After(self.start_id)
+                } else {
+                    api::Cursor::Before(self.start_id)
+                },
+            ),
+        }
+    }
+
+    fn track_job(
+        &mut self,
+        label: &'static str,
+        cancel: Arc<AtomicBool>,
+        rx: Receiver<Progress>,
+        dry_run: bool,
+    ) {
         self.job = Some(ActiveJob {
             label,
             cancel,
@@ -212,13 +516,109 @@ impl App {
             completed: 0,
             total: None,
             stopping: false,
-            dry_run: self.dry_run,
+            dry_run,
             status: "Starting...".to_owned(),
             downloaded_bytes: 0.0,
             started_at: Instant::now(),
+            limit_reached: None,
         });
         self.last_summary = None;
-        self.toast(format!("Starting {label} download..."), ToastKind::Info);
+    }
+
+    fn duplicate_index_path(&self) -> PathBuf {
+        library::index_path(&self.duplicate_index, Path::new(&self.dl_dir))
+    }
+
+    fn start_index_task(&mut self, task: library::IndexTask, label: &'static str) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        library::spawn_index_task(
+            task,
+            self.duplicate_index_path(),
+            self.threads,
+            cancel.clone(),
+            tx,
+        );
+        self.track_job(label, cancel, rx, false);
+        self.index_stats = None;
+    }
+
+    /// The folders listed in the duplicate index section, or the download folder.
+    fn index_scan_folders(&self) -> Vec<PathBuf> {
+        let folders: Vec<PathBuf> = self
+            .index_folders
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        if folders.is_empty() {
+            vec![PathBuf::from(&self.dl_dir)]
+        } else {
+            folders
+        }
+    }
+
+    fn start_audit(&mut self) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let manifest = self.manifest_path.trim();
+        audit::spawn_audit(
+            PathBuf::from(&self.dl_dir),
+            self.duplicate_index_path(),
+            (!manifest.is_empty()).then(|| PathBuf::from(manifest)),
+            self.threads,
+            cancel.clone(),
+            tx,
+        );
+        self.audit_issues.clear();
+        self.track_job("Audit", cancel, rx, false);
+    }
+
+    /// Downloads the broken files found by the audit again, bypassing the tracker that
+    /// still lists them as done.
+    fn repair_audit_issues(&mut self) {
+        let (issues, unknown): (Vec<_>, Vec<_>) = std::mem::take(&mut self.audit_issues)
+            .into_iter()
+            .partition(|issue| issue.post_id.is_some() || issue.md5.is_some());
+        // Files without a post id or md5 stay listed; there is nothing to fetch for them.
+        self.audit_issues = unknown;
+        if issues.is_empty() {
+            self.toast(
+                "Nothing to re-download: no post id or md5 is known for these files.",
+                ToastKind::Warning,
+            );
+            return;
+        }
+        let kind = JobKind::Repair(issues);
+        let settings = self.download_settings(&kind);
+        self.start_job_with(kind, "Repair", settings);
+    }
+
+    /// The tracker lists replaced posts as done, so this job has to bypass it.
+    fn start_replacement_check(&mut self) {
+        let kind = JobKind::CheckReplacements {
+            archive_old: self.archive_replaced,
+        };
+        let mut settings = self.download_settings(&kind);
+        settings.ignore_tracker = true;
+        self.start_job_with(kind, "Replacements", settings);
+    }
+
+    fn show_index_stats(&mut self) {
+        let path = self.duplicate_index_path();
+        match library::stats(&path) {
+            Ok(stats) => {
+                self.index_stats = Some(format!(
+                    "{} entries ({} missing), index file {}, indexed files {}.",
+                    stats.entries,
+                    stats.missing,
+                    format_bytes(stats.index_bytes as f64),
+                    format_bytes(stats.indexed_bytes as f64),
+                ))
+            }
+            Err(error) => self.toast(error, ToastKind::Error),
+        }
     }
 
     fn toast(&mut self, text: impl Into<String>, kind: ToastKind) {
@@ -259,6 +659,23 @@ impl App {
     }
 
     fn load_settings(&mut self) {
+        match settings::load() {
+            Ok(gui) => {
+                self.apply_gui_settings(&gui);
+                self.gui = gui;
+            }
+            Err(e) => self.toast(
+                format!("Could not load GUI settings: {e}"),
+                ToastKind::Warning,
+            ),
+        }
+        match cleanup::load_history() {
+            Ok(history) => self.cleanup_history = history,
+            Err(e) => self.toast(
+                format!("Could not load cleanup history: {e}"),
+                ToastKind::Warning,
+            ),
+        }
         let cfg = match econfig::path().and_then(|p| econfig::load(&p)) {
             Ok(cfg) => cfg,
             Err(e) => {
@@ -270,6 +687,17 @@ impl App {
         self.config = cfg;
     }
 
+    fn apply_gui_settings(&mut self, gui: &settings::GuiSettings) {
+        if let Some(v) = gui.set.set.as_deref() {
+            self.set_id = v.to_owned();
+        }
+        if let Some(v) = gui.set.ordered {
+            self.set_ordered = v;
+        }
+        self.fav_order = gui.orders.favourites.clone();
+        self.search_order = gui.orders.tags.clone();
+    }
+
     fn apply_config(&mut self, cfg: &econfig::Config) {
         if let Some(v) = cfg.global.nsfw {
             self.nsfw = v;
@@ -299,7 +727,7 @@ impl App {
             self.fav_count = v;
         }
         if let Some(v) = cfg.d_favs.random {
-            self.fav_random = v;
+            apply_random(&mut self.fav_order, v);
         }
         if let Some(v) = cfg.d_tags.tags.as_deref() {
             self.search_tags = v.to_owned();
@@ -308,10 +736,10 @@ impl App {
             self.search_count = v;
         }
         if let Some(v) = cfg.d_tags.random {
-            self.search_random = v;
+            apply_random(&mut self.search_order, v);
         }
         if let Some(v) = cfg.d_pool.pool_id {
-            self.pool_id = v.to_string();
+            self.pool_id = pool_list(v, &self.gui.pools);
         }
         if let Some(v) = cfg.zip.name.as_deref() {
             self.zip_name = v.to_owned();
@@ -391,10 +819,12 @@ impl App {
             cfg.d_favs.count = Some(self.fav_count);
             changed = true;
         }
-        if Some(self.fav_random) != cfg.d_favs.random {
-            cfg.d_favs.random = Some(self.fav_random);
+        let random = Some(self.fav_order.as_deref() == Some("random"));
+        if random != cfg.d_favs.random {
+            cfg.d_favs.random = random;
             changed = true;
         }
+        changed |= self.save_order(Tab::Favourites);
         self.finish_save(changed);
     }
 
@@ -409,17 +839,50 @@ impl App {
             cfg.d_tags.count = Some(self.search_count);
             changed = true;
         }
-        if Some(self.search_random) != cfg.d_tags.random {
-            cfg.d_tags.random = Some(self.search_random);
+        let random = Some(self.search_order.as_deref() == Some("random"));
+        if random != cfg.d_tags.random {
+            cfg.d_tags.random = random;
             changed = true;
         }
+        changed |= self.save_order(Tab::Tags);
         self.finish_save(changed);
     }
 
+    /// Keeps a tab's sort order in gui.toml, except Random, which e-cli's config holds.
+    /// Returns whether it changed.
+    fn save_order(&mut self, tab: Tab) -> bool {
+        let (order, stored) = match tab {
+            Tab::Favourites => (&self.fav_order, &mut self.gui.orders.favourites),
+            _ => (&self.search_order, &mut self.gui.orders.tags),
+        };
+        let order = order.clone().filter(|order| order != "random");
+        if order == *stored {
+            return false;
+        }
+        *stored = order;
+        if let Err(e) = settings::save(&self.gui) {
+            self.toast(format!("Could not save settings: {e}"), ToastKind::Error);
+        }
+        true
+    }
+
     fn save_pool(&mut self) {
-        let cfg = &mut self.config;
+        let pool_ids = api::parse_pool_ids(&self.pool_id);
+        let pools = if pool_ids.len() > 1 {
+            pool_ids.clone()
+        } else {
+            Vec::new()
+        };
         let mut changed = false;
-        let pool_id = self.pool_id.trim().parse().ok();
+        if pools != self.gui.pools {
+            self.gui.pools = pools;
+            if let Err(e) = settings::save(&self.gui) {
+                self.toast(format!("Could not save settings: {e}"), ToastKind::Error);
+            }
+            changed = true;
+        }
+        let cfg = &mut self.config;
+        let pool_id = pool_ids.first().copied();
         if pool_id != cfg.d_pool.pool_id {
             cfg.d_pool.pool_id = pool_id;
             changed = true;
@@ -445,6 +908,26 @@ impl App {
         self.finish_save(changed);
     }
 
+    fn save_set(&mut self) {
+        let set = settings::SetSettings {
+            set: if self.set_id.trim().is_empty() {
+                None
+            } else {
+                Some(self.set_id.trim().to_owned())
+            },
+            ordered: Some(self.set_ordered),
+        };
+        if set == self.gui.set {
+            self.toast("No changes to save.", ToastKind::Info);
+            return;
+        }
+        self.gui.set = set;
+        match settings::save(&self.gui) {
+            Ok(()) => self.toast("Settings saved to gui.toml.", ToastKind::Success),
+            Err(e) => self.toast(format!("Could not save settings: {e}"), ToastKind::Error),
+        }
+    }
+
     fn load_preset(&mut self) {
         let Some(preset) = self.config.presets.get(&self.preset_name).cloned() else {
             self.toast("Select a saved preset first.", ToastKind::Warning);
@@ -453,13 +936,21 @@ impl App {
         self.preset_source = match preset.source.as_deref() {
             Some("favourites") => PresetSource::Favourites,
             Some("pool") => PresetSource::Pool,
+            Some("set") => PresetSource::Set,
             _ => PresetSource::Tags,
         };
+        let gui_preset = self
+            .gui
+            .presets
+            .get(&self.preset_name)
+            .cloned()
+            .unwrap_or_default();
         match self.preset_source {
             PresetSource::Tags => {
                 if let Some(tags) = preset.tags {
                     self.search_tags = tags;
                 }
+                self.search_order = preset_order(preset.random, &gui_preset);
             }
             PresetSource::Favourites => {
                 if let Some(username) = preset.username {
@@ -468,10 +959,19 @@ impl App {
                 if let Some(tags) = preset.fav_tags {
                     self.fav_tags = tags;
                 }
+                self.fav_order = preset_order(preset.random, &gui_preset);
             }
             PresetSource::Pool => {
                 if let Some(pool_id) = preset.pool_id {
-                    self.pool_id = pool_id.to_string();
+                    self.pool_id = pool_list(pool_id, &gui_preset.pools);
+                }
+            }
+            PresetSource::Set => {
+                if let Some(set) = gui_preset.set {
+                    self.set_id = set;
+                }
+                if let Some(ordered) = gui_preset.set_ordered {
+                    self.set_ordered = ordered;
                 }
             }
         }
@@ -493,6 +993,10 @@ impl App {
         if let Some(dir) = preset.dir {
             self.dl_dir = dir;
         }
+        // A preset without limits means an unlimited job.
+        self.quota_max_mb = gui_preset.max_mb.unwrap_or(0);
+        self.quota_max_files = gui_preset.max_files.unwrap_or(0);
+        self.quota_max_minutes = gui_preset.max_minutes.unwrap_or(0);
         self.toast(
             format!("Loaded preset '{}'.", self.preset_name),
             ToastKind::Success,
@@ -504,6 +1008,10 @@ impl App {
             self.toast("Enter a preset name first.", ToastKind::Warning);
             return;
         }
+        let pool_ids = match self.preset_source {
+            PresetSource::Pool => api::parse_pool_ids(&self.pool_id),
+            _ => Vec::new(),
+        };
         let (tags, fav_tags, username, pool_id) = match self.preset_source {
             PresetSource::Tags => (Some(self.search_tags.clone()), None, None, None),
             PresetSource::Favourites => (
@@ -512,8 +1020,47 @@ impl App {
                 Some(self.username.clone()),
                 None,
             ),
-            PresetSource::Pool => (None, None, None, self.pool_id.trim().parse().ok()),
+            PresetSource::Pool => (None, None, None, pool_ids.first().copied()),
+            PresetSource::Set => (None, None, None, None),
         };
+        let mut gui_preset = match self.preset_source {
+            PresetSource::Set => settings::GuiPreset {
+                set: Some(self.set_id.trim().to_owned()),
+                set_ordered: Some(self.set_ordered),
+                ..Default::default()
+            },
+            PresetSource::Favourites => settings::GuiPreset {
+                order: self.fav_order.clone(),
+                ..Default::default()
+            },
+            PresetSource::Tags => settings::GuiPreset {
+                order: self.search_order.clone(),
+                ..Default::default()
+            },
+            PresetSource::Pool => settings::GuiPreset {
+                pools: if pool_ids.len() > 1 {
+                    pool_ids
+                } else {
+                    Vec::new()
+                },
+                ..Default::default()
+            },
+        };
+        let random = gui_preset.order.as_deref() == Some("random");
+        gui_preset.max_mb = (self.quota_max_mb > 0).then_some(self.quota_max_mb);
+        gui_preset.max_files = (self.quota_max_files > 0).then_some(self.quota_max_files);
+        gui_preset.max_minutes = (self.quota_max_minutes > 0).then_some(self.quota_max_minutes);
+        if gui_preset == settings::GuiPreset::default() {
+            self.gui.presets.remove(self.preset_name.trim());
+        } else {
+            self.gui
+                .presets
+                .insert(self.preset_name.trim().to_owned(), gui_preset);
+        }
+        if let Err(error) = settings::save(&self.gui) {
+            self.toast(format!("Could not save preset: {error}"), ToastKind::Error);
+            return;
+        }
         self.config.presets.insert(
             self.preset_name.trim().to_owned(),
             econfig::PresetConfig {
@@ -530,7 +1077,7 @@ impl App {
                 lower_quality: Some(self.lower_quality),
                 nsfw: Some(self.nsfw),
                 dir: Some(self.dl_dir.clone()),
-                ..Default::default()
+                random: Some(random),
             },
         );
         match econfig::save(&self.config) {
@@ -571,10 +1118,14 @@ impl App {
                             self.dl_dir,
                         )
                     } else {
-                        format!(
+                        let mut message = format!(
                             "Finished! {} downloaded, {} skipped, {} failed (of {}).",
                             stats.completed, stats.skipped, stats.failed, stats.total
-                        )
+                        );
+                        if let Some(limit) = &job.limit_reached {
+                            message.push_str(&format!(" Stopped at the {limit}."));
+                        }
+                        message
                     };
                     if job.dry_run {
                         self.last_summary = Some(message);
@@ -582,6 +1133,24 @@ impl App {
                         finished_msg = Some((message, ToastKind::Success));
                     }
                 }
+                Progress::SimilarGroups(groups) => self.similar_groups = groups,
+                Progress::Audit(issues) => self.audit_issues = issues,
+                Progress::Mirror(stale) => {
+                    self.mirror_bytes = total_size(&stale);
+                    self.mirror_stale = Some(stale);
+                    self.mirror_confirm = false;
+                }
+                Progress::LimitReached(limit) => {
+                    job.status = format!("Reached the {limit}, finishing...");
+                    job.limit_reached = Some(limit);
+                }
+                Progress::Warning(warning) => {
+                    self.pending_toasts.push((warning, ToastKind::Warning));
+                }
+                Progress::Summary(summary) => {
+                    finished_msg = Some((summary.clone(), ToastKind::Success));
+                    self.last_summary = Some(summary);
+                }
                 Progress::Cancelled => {
                     finished_msg = Some(("Download cancelled.".to_owned(), ToastKind::Info));
                 }
@@ -604,15 +1173,240 @@ impl App {
         }
     }
 
-    fn poll_zip(&mut self) {
-        let Some(zip_job) = &self.zip_job else { return };
-        if let Ok(ZipEvent::Finished(ok)) = zip_job.rx.try_recv() {
-            if ok {
-                self.toast("Archive created!", ToastKind::Success);
+    fn spawn_pool_search(&mut self, ctx: egui::Context) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let nsfw = self.nsfw;
+        let login = e_cli::Login {
+            username: self.username.clone(),
+            api_key: self.api_key.clone(),
+        };
+        let name = self.pool_search_name.clone();
+        let creator = self.pool_search_creator.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(api::search_pools(nsfw, &login, &name, &creator));
+            ctx.request_repaint();
+        });
+        self.pool_search_rx = Some(rx);
+    }
+
+    fn poll_pool_search(&mut self) {
+        let Some(rx) = self.pool_search_rx.take() else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(Ok(pools)) => {
+                if pools.is_empty() {
+                    self.toast("No pools found.", ToastKind::Info);
+                }
+                self.pool_results = pools.into_iter().map(|pool| (pool, false)).collect();
+            }
+            Ok(Err(error)) => self.toast(error, ToastKind::Error),
+            Err(_) => self.pool_search_rx = Some(rx),
+        }
+    }
+
+    fn spawn_artist_refresh(&mut self, ctx: egui::Context) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let nsfw = self.nsfw;
+        let login = e_cli::Login {
+            username: self.username.clone(),
+            api_key: self.api_key.clone(),
+        };
+        let dl_dir = PathBuf::from(&self.dl_dir);
+        let names = self.gui.watchlist.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(watchlist::fetch_status(nsfw, &login, &dl_dir, &names));
+            ctx.request_repaint();
+        });
+        self.artist_rx = Some(rx);
+    }
+
+    fn poll_artist_status(&mut self) {
+        let Some(rx) = self.artist_rx.take() else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(status) => self.artist_status = status,
+            Err(_) => self.artist_rx = Some(rx),
+        }
+    }
+
+    fn save_watchlist(&mut self) {
+        if let Err(e) = settings::save(&self.gui) {
+            self.toast(format!("Could not save watchlist: {e}"), ToastKind::Error);
+        }
+    }
+
+    /// Imports `dumps` (if any) and then loads the tag database on a background thread.
+    fn spawn_tag_db_load(&mut self, ctx: egui::Context, dumps: Vec<PathBuf>) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let summary = if dumps.is_empty() {
+                Ok(String::new())
             } else {
-                self.toast("Failed to create archive. Is 7z on PATH?", ToastKind::Error);
+                tagdb::import_dumps(&dumps)
+            };
+            let _ = tx.send(summary.and_then(|summary| Ok((tagdb::TagDb::load()?, summary))));
+            ctx.request_repaint();
+        });
+        self.tag_db_rx = Some(rx);
+    }
+
+    fn poll_tag_db(&mut self) {
+        if let Some(rx) = self.tag_db_rx.take() {
+            match rx.try_recv() {
+                Ok(Ok((db, summary))) => {
+                    self.tag_assist.db = db;
+                    self.tag_assist.shown = None;
+                    if !summary.is_empty() {
+                        self.toast(summary, ToastKind::Success);
+                    }
+                }
+                Ok(Err(error)) => self.toast(error, ToastKind::Error),
+                Err(_) => self.tag_db_rx = Some(rx),
+            }
+        }
+        if let Some(rx) = self.relations_rx.take() {
+            match rx.try_recv() {
+                Ok(Ok((aliases, implications))) => {
+                    let message = format!(
+                        "Fetched {} aliases and {} implications.",
+                        aliases.len(),
+                        implications.len()
+                    );
+                    self.tag_assist.shown = None;
+                    match self.tag_assist.db.merge_relations(aliases, implications) {
+                        Ok(()) => self.toast(message, ToastKind::Success),
+                        Err(error) => self.toast(error, ToastKind::Error),
+                    }
+                }
+                Ok(Err(error)) => self.toast(error, ToastKind::Error),
+                Err(_) => self.relations_rx = Some(rx),
+            }
+        }
+        if let Some(rx) = self.tag_assist.live_rx.take() {
+            match rx.try_recv() {
+                Ok(live) => {
+                    self.tag_assist.live = Some(live);
+                    self.tag_assist.shown = None;
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => self.tag_assist.live_rx = Some(rx),
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {}
+            }
+        }
+    }
+
+    /// Asks the site for suggestions, one request at a time. Failures just leave the local
+    /// suggestions on their own.
+    fn spawn_tag_lookup(&mut self, prefix: String, ctx: egui::Context) {
+        if self.tag_assist.live_rx.is_some() {
+            return;
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        let nsfw = self.nsfw;
+        let login = e_cli::Login {
+            username: self.username.clone(),
+            api_key: self.api_key.clone(),
+        };
+        std::thread::spawn(move || {
+            let found = api::autocomplete_tags(nsfw, &login, &prefix).unwrap_or_default();
+            let _ = tx.send((prefix, found));
+            ctx.request_repaint();
+        });
+        self.tag_assist.live_rx = Some(rx);
+    }
+
+    fn spawn_relations_refresh(&mut self, tags: &str, ctx: egui::Context) {
+        let names: Vec<String> = query::tokenize(tags)
+            .into_iter()
+            .filter(|token| token.kind == query::TokenKind::Tag)
+            .map(|token| token.name)
+            .filter(|name| !name.is_empty() && !name.contains('*'))
+            .collect();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let nsfw = self.nsfw;
+        let login = e_cli::Login {
+            username: self.username.clone(),
+            api_key: self.api_key.clone(),
+        };
+        std::thread::spawn(move || {
+            let _ = tx.send(api::tag_relations(nsfw, &login, &names));
+            ctx.request_repaint();
+        });
+        self.relations_rx = Some(rx);
+    }
+
+    /// Parses dropped or picked post lists into one preview on the Posts tab.
+    fn import_post_files(&mut self, paths: &[PathBuf]) {
+        let mut refs = api::PostRefs::default();
+        let mut sources = Vec::new();
+        for path in paths {
+            match import::post_refs_from_file(path) {
+                Ok(file_refs) => {
+                    for id in file_refs.ids {
+                        if !refs.ids.contains(&id) {
+                            refs.ids.push(id);
+                        }
+                    }
+                    for md5 in file_refs.md5s {
+                        if !refs.md5s.contains(&md5) {
+                            refs.md5s.push(md5);
+                        }
+                    }
+                    refs.unrecognised += file_refs.unrecognised;
+                    sources.push(
+                        path.file_name()
+                            .map(|name| name.to_string_lossy().to_string())
+                            .unwrap_or_else(|| path.display().to_string()),
+                    );
+                }
+                Err(error) => self.toast(error, ToastKind::Error),
+            }
+        }
+        if sources.is_empty() {
+            return;
+        }
+        let tracked_ids = import::tracked_ids(&self.track_file);
+        let tracked = refs
+            .ids
+            .iter()
+            .filter(|id| tracked_ids.contains(id))
+            .count();
+        self.import_preview = Some(ImportPreview {
+            source: sources.join(", "),
+            refs,
+            tracked,
+        });
+        self.tab = Tab::Posts;
+    }
+
+    fn poll_zip(&mut self) {
+        let Some(zip_job) = &mut self.zip_job else {
+            return;
+        };
+        while let Ok(event) = zip_job.rx.try_recv() {
+            match event {
+                ZipEvent::Volume { index, total } => {
+                    zip_job.status = format!("Packaging volume {index}/{total}...");
+                }
+                ZipEvent::Finished(ok) => {
+                    if ok {
+                        self.toast("Archive created!", ToastKind::Success);
+                    } else {
+                        self.toast("Failed to create archive. Is 7z on PATH?", ToastKind::Error);
+                    }
+                    self.zip_job = None;
+                    return;
+                }
             }
-            self.zip_job = None;
+        }
+    }
+
+    fn archive_split(&self) -> ArchiveSplit {
+        match self.zip_split {
+            SplitMode::None => ArchiveSplit::None,
+            SplitMode::Pages => ArchiveSplit::Pages(self.zip_split_pages),
+            SplitMode::Size => ArchiveSplit::Size(self.zip_split_mb * 1024 * 1024),
         }
     }
 }
@@ -621,7 +1415,22 @@ impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.poll_job(ctx);
         self.poll_zip();
+        self.poll_pool_search();
+        self.poll_artist_status();
+        self.poll_trash();
         self.poll_version_check();
+        self.poll_tag_db();
+
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        if !dropped.is_empty() {
+            self.import_post_files(&dropped);
+        }
 
         let mut toasts = Toasts::new()
             .anchor(Align2::CENTER_BOTTOM, (0.0, -8.0))
@@ -640,10 +1449,14 @@ impl eframe::App for App {
 
         egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
             ui.add_space(4.0);
-            ui.horizontal(|ui| {
+            ui.horizontal_wrapped(|ui| {
                 ui.selectable_value(&mut self.tab, Tab::Favourites, "Favourites");
                 ui.selectable_value(&mut self.tab, Tab::Tags, "Tags");
                 ui.selectable_value(&mut self.tab, Tab::Pool, "Pool");
+                ui.selectable_value(&mut self.tab, Tab::Set, "Set");
+                ui.selectable_value(&mut self.tab, Tab::Posts, "Posts");
+                ui.selectable_value(&mut self.tab, Tab::Popular, "Popular");
+                ui.selectable_value(&mut self.tab, Tab::Artists, "Artists");
                 ui.selectable_value(&mut self.tab, Tab::Utilities, "Utilities");
                 ui.selectable_value(&mut self.tab, Tab::Config, "Config");
             });
@@ -660,9 +1473,30 @@ impl eframe::App for App {
             Tab::Favourites => self.favourites_ui(ui),
             Tab::Tags => self.tags_ui(ui),
             Tab::Pool => self.pool_ui(ui),
+            Tab::Set => self.set_ui(ui),
+            Tab::Posts => self.posts_ui(ui),
+            Tab::Popular => self.popular_ui(ui),
+            Tab::Artists => self.artists_ui(ui),
             Tab::Utilities => self.utilities_ui(ui),
             Tab::Config => self.config_ui(ui),
         });
+        self.cleanup_window(ctx);
+
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("drop_overlay"),
+            ));
+            let screen = ctx.content_rect();
+            painter.rect_filled(screen, 0.0, Color32::from_black_alpha(192));
+            painter.text(
+                screen.center(),
+                Align2::CENTER_CENTER,
+                "Drop post lists to import",
+                egui::TextStyle::Heading.resolve(&ctx.style()),
+                Color32::WHITE,
+            );
+        }
 
         toasts.show(ctx);
     }
@@ -717,9 +1551,21 @@ impl App {
                     ui.text_edit_singleline(&mut self.dl_dir);
                 });
                 ui.add(egui::Slider::new(&mut self.threads, 1..=10).text("Threads"));
-                ui.add(egui::Slider::new(&mut self.pages, -1..=75).text("Pages (-1 = all)"));
-                ui.add(egui::Slider::new(&mut self.retries, 0..=10).text("Retries"));
-                ui.checkbox(&mut self.lower_quality, "Prefer lower quality");
+                ui.add(egui::Slider::new(&mut self.pages, -1..=1000).text("Pages (-1 = all)"))
+                    .on_hover_text(
+                        "Searches sorted by post id (Default, Newest or Oldest) page by post id \
+                         when fetching everything or more than the site's 750 numbered pages",
+                    );
+                ui.horizontal(|ui| {
+                    ui.label("Start at post id (0 = newest)");
+                    ui.add(egui::DragValue::new(&mut self.start_id));
+                    ui.add_enabled_ui(self.start_id > 0, |ui| {
+                        ui.radio_value(&mut self.start_newer, false, "older posts");
+                        ui.radio_value(&mut self.start_newer, true, "newer posts");
+                    });
+                });
+                ui.add(egui::Slider::new(&mut self.retries, 0..=10).text("Retries"));
+                ui.checkbox(&mut self.lower_quality, "Prefer lower quality");
                 ui.checkbox(
                     &mut self.dry_run,
                     "Dry run (no files or local state written)",
@@ -738,6 +1584,31 @@ impl App {
                             .hint_text("blank = download folder default"),
                     );
                 });
+                ui.checkbox(
+                    &mut self.skip_near_duplicates,
+                    "Skip near-duplicates (compares post previews)",
+                );
+                ui.add_enabled(
+                    self.skip_near_duplicates,
+                    egui::Slider::new(&mut self.near_duplicate_threshold, 0..=16)
+                        .text("Similarity threshold (bits)"),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Keep free (MB)");
+                    ui.add(egui::DragValue::new(&mut self.min_free_mb).range(0..=1_048_576))
+                        .on_hover_text("Downloads pause while the drive has less free; 0 = off");
+                    ui.checkbox(&mut self.refuse_low_space, "Refuse jobs that won't fit");
+                });
+                ui.label("Job limits (0 = no limit)");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.quota_max_mb)
+                            .range(0..=1_048_576)
+                            .suffix(" MB"),
+                    );
+                    ui.add(egui::DragValue::new(&mut self.quota_max_files).suffix(" files"));
+                    ui.add(egui::DragValue::new(&mut self.quota_max_minutes).suffix(" min"));
+                });
                 ui.horizontal(|ui| {
                     ui.label("Failure manifest (.json)");
                     ui.add(
@@ -792,6 +1663,11 @@ impl App {
                                 PresetSource::Pool,
                                 "Pool",
                             );
+                            ui.selectable_value(
+                                &mut self.preset_source,
+                                PresetSource::Set,
+                                "Set",
+                            );
                         });
                 });
                 ui.horizontal(|ui| {
@@ -839,11 +1715,52 @@ impl App {
             ui.label("Username");
             ui.text_edit_singleline(&mut self.username);
         });
+        ui.horizontal(|ui| {
+            ui.label("Favourites of");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.fav_users)
+                    .hint_text("blank = Username; several separated by commas"),
+            );
+        });
+        let users = self.fav_user_list();
+        if users.len() > 1 {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.fav_all, false, "Favourited by any of them");
+                ui.radio_value(&mut self.fav_all, true, "Favourited by all of them")
+                    .on_hover_text(
+                        "Fetches every page of each user's favourites to compare them; \
+                         Pages then limits the pages of shared posts downloaded",
+                    );
+            });
+            ui.add_enabled(
+                !self.fav_all,
+                egui::Checkbox::new(
+                    &mut self.fav_per_user,
+                    "Separate folder per user (fav_<username>/)",
+                ),
+            )
+            .on_hover_text(
+                "A post several users favourited goes to the first listed user's folder",
+            );
+        }
         ui.add_space(6.0);
-        tags_edit(ui, &mut self.fav_tags);
+        if let Some(prefix) = tags_edit(ui, &mut self.fav_tags, &mut self.tag_assist) {
+            self.spawn_tag_lookup(prefix, ui.ctx().clone());
+        }
         ui.add_space(6.0);
         ui.add(egui::Slider::new(&mut self.fav_count, 1..=250).text("Posts per page"));
-        ui.checkbox(&mut self.fav_random, "Random order");
+        order_combo(ui, "fav_order", &mut self.fav_order);
+        query_warnings(
+            ui,
+            &self.fav_tags,
+            self.fav_order.as_deref(),
+            1,
+            &self.tag_assist.db,
+        );
+        let tags = self.fav_tags.clone();
+        self.resolution_ui(ui, &tags);
+        self.query_builder_ui(ui, Tab::Favourites);
+        mirror_checkbox(ui, &mut self.fav_mirror, self.pages);
         ui.add_space(10.0);
 
         if ui.button("Save settings").clicked() {
@@ -851,23 +1768,63 @@ impl App {
         }
 
         let busy = self.job.is_some();
-        ui.add_enabled_ui(!busy && !self.username.trim().is_empty(), |ui| {
+        ui.add_enabled_ui(!busy && !users.is_empty(), |ui| {
             if ui.button("Download Favourites").clicked() {
-                self.start_job(JobKind::Favourites, "Favourites");
+                let several = users.len() > 1;
+                self.start_job(
+                    JobKind::Favourites {
+                        users,
+                        all: several && self.fav_all,
+                        per_user: several && !self.fav_all && self.fav_per_user,
+                    },
+                    "Favourites",
+                );
             }
         });
         if busy {
             self.stop_button(ui);
         }
+        self.mirror_ui(ui);
+    }
+
+    /// The users named in "Favourites of", or the account's own username.
+    fn fav_user_list(&self) -> Vec<String> {
+        let mut users: Vec<String> = Vec::new();
+        for user in self
+            .fav_users
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|user| !user.is_empty())
+        {
+            if !users.iter().any(|known| known.eq_ignore_ascii_case(user)) {
+                users.push(user.to_owned());
+            }
+        }
+        if users.is_empty() && !self.username.trim().is_empty() {
+            users.push(self.username.trim().to_owned());
+        }
+        users
     }
 
     fn tags_ui(&mut self, ui: &mut egui::Ui) {
         ui.heading("Download by Tags");
         ui.add_space(8.0);
-        tags_edit(ui, &mut self.search_tags);
+        if let Some(prefix) = tags_edit(ui, &mut self.search_tags, &mut self.tag_assist) {
+            self.spawn_tag_lookup(prefix, ui.ctx().clone());
+        }
         ui.add_space(6.0);
         ui.add(egui::Slider::new(&mut self.search_count, 1..=250).text("Posts per page"));
-        ui.checkbox(&mut self.search_random, "Random order");
+        order_combo(ui, "search_order", &mut self.search_order);
+        query_warnings(
+            ui,
+            &self.search_tags,
+            self.search_order.as_deref(),
+            0,
+            &self.tag_assist.db,
+        );
+        let tags = self.search_tags.clone();
+        self.resolution_ui(ui, &tags);
+        self.query_builder_ui(ui, Tab::Tags);
+        mirror_checkbox(ui, &mut self.search_mirror, self.pages);
         ui.add_space(10.0);
 
         if ui.button("Save settings").clicked() {
@@ -883,27 +1840,631 @@ impl App {
         if busy {
             self.stop_button(ui);
         }
+        self.union_ui(ui);
+        self.mirror_ui(ui);
     }
 
-    fn pool_ui(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Download a Pool");
-        ui.add_space(8.0);
+    /// Several queries downloaded as one job, for searches over the tag limit.
+    fn union_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Union of several queries").show(ui, |ui| {
+            ui.label(
+                RichText::new(
+                    "One query per line. Posts matching any of them are downloaded once.",
+                )
+                .weak(),
+            );
+            ui.add(
+                egui::TextEdit::multiline(&mut self.union_queries)
+                    .desired_rows(4)
+                    .desired_width(f32::INFINITY),
+            );
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        !self.search_tags.trim().is_empty(),
+                        egui::Button::new("Split tags field"),
+                    )
+                    .on_hover_text("Divide the '~' tags between queries that fit the tag limit")
+                    .clicked()
+                {
+                    let split = query::split_any_of(
+                        &self.search_tags,
+                        usize::from(self.search_order.is_some()),
+                    );
+                    if split.len() > 1 {
+                        self.union_queries = split.join("\n");
+                    } else {
+                        self.toast(
+                            "The tags field can't be split: it fits the tag limit, has no '~' \
+                             tags or its other tags leave no room.",
+                            ToastKind::Info,
+                        );
+                    }
+                }
+                let count = self
+                    .union_queries
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .count();
+                let busy = self.job.is_some();
+                if ui
+                    .add_enabled(
+                        !busy && count > 0,
+                        egui::Button::new(format!("Download union ({count} queries)")),
+                    )
+                    .clicked()
+                {
+                    let queries: Vec<String> = self
+                        .union_queries
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(|line| {
+                            if self.resolve_aliases {
+                                query::resolve(line, &self.tag_assist.db, self.drop_implied).query
+                            } else {
+                                line.to_owned()
+                            }
+                        })
+                        .collect();
+                    self.start_job(JobKind::Union(queries), "Union");
+                }
+            });
+        });
+    }
+
+    /// Alias/implication options under a tags field, and what they would change.
+    fn resolution_ui(&mut self, ui: &mut egui::Ui, tags: &str) {
+        ui.horizontal_wrapped(|ui| {
+            ui.checkbox(&mut self.resolve_aliases, "Resolve aliases");
+            ui.add_enabled(
+                self.resolve_aliases,
+                egui::Checkbox::new(&mut self.drop_implied, "Drop implied tags"),
+            )
+            .on_hover_text(
+                "Remove tags that another tag in the query already implies; they match \
+                 nothing extra and count against the tag limit",
+            );
+            let refreshing = self.relations_rx.is_some();
+            if ui
+                .add_enabled(
+                    !refreshing && !tags.trim().is_empty(),
+                    egui::Button::new("Refresh from site"),
+                )
+                .on_hover_text("Fetch the aliases and implications of these tags")
+                .clicked()
+            {
+                self.spawn_relations_refresh(tags, ui.ctx().clone());
+            }
+            if refreshing {
+                ui.spinner();
+            }
+        });
+        if !self.resolve_aliases {
+            return;
+        }
+        let resolved = query::resolve(tags, &self.tag_assist.db, self.drop_implied);
+        if resolved.changes.is_empty() {
+            return;
+        }
+        ui.label(RichText::new(format!("Will search: {}", resolved.query)).weak());
+        for change in resolved.changes {
+            ui.label(RichText::new(change).small().weak());
+        }
+    }
+
+    /// Structured editor that reads and writes the tags field of the Favourites or Tags
+    /// tab.
+    fn query_builder_ui(&mut self, ui: &mut egui::Ui, tab: Tab) {
+        egui::CollapsingHeader::new("Query builder")
+            .id_salt(("query_builder", tab == Tab::Favourites))
+            .show(ui, |ui| {
+                let tags = match tab {
+                    Tab::Favourites => &mut self.fav_tags,
+                    _ => &mut self.search_tags,
+                };
+                let builder = &mut self.query_builder;
+                if ui.button("Read from tags").clicked() {
+                    *builder = query::QueryParts::parse(tags);
+                }
+                egui::Grid::new(("query_builder_grid", tab == Tab::Favourites))
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Include");
+                        ui.text_edit_singleline(&mut builder.include);
+                        ui.end_row();
+                        ui.label("Exclude");
+                        ui.text_edit_singleline(&mut builder.exclude);
+                        ui.end_row();
+                        let mut remove = None;
+                        for (i, group) in builder.any_of.iter_mut().enumerate() {
+                            ui.label("Any of");
+                            ui.horizontal(|ui| {
+                                ui.text_edit_singleline(group);
+                                if ui.small_button("x").clicked() {
+                                    remove = Some(i);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                        if let Some(i) = remove {
+                            builder.any_of.remove(i);
+                        }
+                        ui.label("");
+                        if ui.small_button("Add any-of group").clicked() {
+                            builder.any_of.push(String::new());
+                        }
+                        ui.end_row();
+                        ui.label("Rating");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut builder.ratings[0], "Safe");
+                            ui.checkbox(&mut builder.ratings[1], "Questionable");
+                            ui.checkbox(&mut builder.ratings[2], "Explicit");
+                        });
+                        ui.end_row();
+                        ui.label("Score");
+                        range_edit(ui, &mut builder.score);
+                        ui.end_row();
+                        ui.label("Favourites");
+                        range_edit(ui, &mut builder.favcount);
+                        ui.end_row();
+                        ui.label("Date");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut builder.date.0)
+                                    .desired_width(90.0)
+                                    .hint_text("YYYY-MM-DD"),
+                            );
+                            ui.label("to");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut builder.date.1)
+                                    .desired_width(90.0)
+                                    .hint_text("YYYY-MM-DD"),
+                            );
+                        });
+                        ui.end_row();
+                        ui.label("File type");
+                        option_combo(
+                            ui,
+                            "query_file_type",
+                            &mut builder.file_type,
+                            query::FILE_TYPES,
+                        );
+                        ui.end_row();
+                        ui.label("Sort");
+                        order_combo(ui, "query_order", &mut builder.order);
+                        ui.end_row();
+                        ui.label("Other");
+                        ui.text_edit_singleline(&mut builder.other);
+                        ui.end_row();
+                    });
+                let generated = builder.to_query();
+                ui.label(RichText::new(&generated).weak());
+                if ui.button("Write to tags").clicked() {
+                    *tags = generated;
+                }
+            });
+    }
+
+    /// The mirror report from the last Favourites/Tags job, with a confirmed trash step.
+    fn mirror_ui(&mut self, ui: &mut egui::Ui) {
+        if matches!(self.trash_rx, Some((TrashOrigin::Mirror, _))) {
+            ui.add_space(10.0);
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Mirror: moving files to the trash...");
+            });
+            return;
+        }
+        let Some(stale) = &self.mirror_stale else {
+            return;
+        };
+        ui.add_space(10.0);
+        ui.separator();
+        if stale.is_empty() {
+            ui.label("Mirror: every file this query downloaded is still in the result.");
+            if ui.button("Dismiss").clicked() {
+                self.mirror_stale = None;
+            }
+            return;
+        }
+        ui.label(format!(
+            "Mirror: {} files ({}) this query downloaded are no longer in the result.",
+            stale.len(),
+            format_bytes(self.mirror_bytes as f64)
+        ));
+        egui::ScrollArea::vertical()
+            .id_salt("mirror_stale")
+            .max_height(140.0)
+            .show(ui, |ui| {
+                for file in stale {
+                    ui.label(RichText::new(file.display().to_string()).weak());
+                }
+            });
+        let mut trash_now = false;
+        let trashing = self.trash_rx.is_some();
         ui.horizontal(|ui| {
-            ui.label("Pool ID");
-            ui.text_edit_singleline(&mut self.pool_id);
+            if self.mirror_confirm {
+                ui.label("Move them to the trash?");
+                trash_now = ui
+                    .add_enabled(!trashing, egui::Button::new("Yes, trash them"))
+                    .clicked();
+                if ui.button("Cancel").clicked() {
+                    self.mirror_confirm = false;
+                }
+            } else {
+                if ui
+                    .add(egui::Button::new("Move to trash").fill(Color32::from_rgb(125, 0, 0)))
+                    .clicked()
+                {
+                    self.mirror_confirm = true;
+                }
+                if ui.button("Dismiss").clicked() {
+                    self.mirror_stale = None;
+                }
+            }
+        });
+        if trash_now {
+            let stale = self.mirror_stale.take().unwrap_or_default();
+            self.mirror_confirm = false;
+            self.spawn_trash(
+                TrashOrigin::Mirror,
+                stale,
+                "Mirror: files no longer in the result".to_owned(),
+                ui.ctx().clone(),
+            );
+        }
+    }
+
+    /// Moves `targets` to the trash on a worker thread; [`Self::poll_trash`] takes the
+    /// outcome and records it in the cleanup history.
+    fn spawn_trash(
+        &mut self,
+        origin: TrashOrigin,
+        targets: Vec<PathBuf>,
+        description: String,
+        ctx: egui::Context,
+    ) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let index_path = self.duplicate_index_path();
+        std::thread::spawn(move || {
+            let _ = tx.send(cleanup::trash_recorded(&targets, &index_path, description));
+            ctx.request_repaint();
         });
+        self.trash_rx = Some((origin, rx));
+    }
+
+    fn poll_trash(&mut self) {
+        let Some((origin, rx)) = self.trash_rx.take() else {
+            return;
+        };
+        let trashed = match rx.try_recv() {
+            Ok(Ok(trashed)) => trashed,
+            Ok(Err(error)) => {
+                self.toast(error, ToastKind::Error);
+                return;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => {
+                self.trash_rx = Some((origin, rx));
+                return;
+            }
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => return,
+        };
+        let moved = trashed
+            .record
+            .as_ref()
+            .map_or(0, |record| record.paths.len());
+        if let Some(record) = trashed.record {
+            self.cleanup_history.push(record);
+            if let Err(error) = cleanup::save_history(&self.cleanup_history) {
+                self.toast(error, ToastKind::Error);
+            }
+        }
+        match origin {
+            TrashOrigin::Mirror if trashed.failed.is_empty() => self.toast(
+                format!("Moved {moved} files to the trash."),
+                ToastKind::Success,
+            ),
+            TrashOrigin::Mirror => {
+                self.toast(
+                    format!("{} files could not be trashed.", trashed.failed.len()),
+                    ToastKind::Warning,
+                );
+                self.mirror_bytes = total_size(&trashed.failed);
+                self.mirror_stale = Some(trashed.failed);
+            }
+            TrashOrigin::Cleanup if trashed.failed.is_empty() => {
+                self.cleanup_open = false;
+                self.toast("Cleaned up!", ToastKind::Info);
+            }
+            TrashOrigin::Cleanup => self.toast(
+                format!("{} items could not be trashed.", trashed.failed.len()),
+                ToastKind::Warning,
+            ),
+        }
+    }
+
+    /// Cleanup dialog: pick what to remove, preview it, then confirm before anything is
+    /// moved to the trash.
+    fn cleanup_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.cleanup_open;
+        let mut changed = false;
+        let mut preview = false;
+        let mut trash_now = false;
+        let mut mode_changed = false;
+        let trashing = matches!(self.trash_rx, Some((TrashOrigin::Cleanup, _)));
+        egui::Window::new("Cleanup")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!("Remove from {}:", self.dl_dir));
+                for mode in cleanup::CleanupMode::ALL {
+                    mode_changed |= ui
+                        .radio_value(&mut self.cleanup_mode, mode, mode.label())
+                        .changed();
+                }
+                changed |= mode_changed;
+                ui.add_space(4.0);
+                match self.cleanup_mode {
+                    cleanup::CleanupMode::OlderThan => {
+                        ui.horizontal(|ui| {
+                            ui.label("Days");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut self.cleanup_days).range(0..=3650))
+                                .changed();
+                        });
+                    }
+                    cleanup::CleanupMode::Subfolder => {
+                        egui::ComboBox::from_id_salt("cleanup_subfolder")
+                            .selected_text(self.cleanup_subfolder.as_str())
+                            .show_ui(ui, |ui| {
+                                for folder in &self.cleanup_subfolders {
+                                    changed |= ui
+                                        .selectable_value(
+                                            &mut self.cleanup_subfolder,
+                                            folder.clone(),
+                                            folder,
+                                        )
+                                        .changed();
+                                }
+                            });
+                    }
+                    cleanup::CleanupMode::TagRule => {
+                        changed |= ui
+                            .add(
+                                egui::TextEdit::singleline(&mut self.cleanup_tag_rule)
+                                    .hint_text("e.g. solo -comic"),
+                            )
+                            .on_hover_text(
+                                "Matched against each file's .json/.txt sidecar; -tag excludes",
+                            )
+                            .changed();
+                    }
+                    cleanup::CleanupMode::Everything | cleanup::CleanupMode::Partial => {}
+                }
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    preview = ui
+                        .add_enabled(self.cleanup_plan_rx.is_none(), egui::Button::new("Preview"))
+                        .clicked();
+                    if self.cleanup_plan_rx.is_some() {
+                        ui.spinner();
+                        ui.label("Scanning...");
+                    }
+                });
+
+                let Some(plan) = &self.cleanup_plan else {
+                    return;
+                };
+                ui.separator();
+                ui.label(format!(
+                    "{} files ({}) will be moved to the trash.",
+                    plan.files,
+                    format_bytes(plan.bytes as f64)
+                ));
+                egui::ScrollArea::vertical()
+                    .id_salt("cleanup_targets")
+                    .max_height(140.0)
+                    .show(ui, |ui| {
+                        for target in &plan.targets {
+                            ui.label(RichText::new(target.display().to_string()).weak());
+                        }
+                    });
+                if plan.targets.is_empty() {
+                    return;
+                }
+                ui.checkbox(
+                    &mut self.cleanup_confirm,
+                    "I understand these will be trashed",
+                );
+                ui.add_enabled_ui(self.cleanup_confirm && self.trash_rx.is_none(), |ui| {
+                    ui.horizontal(|ui| {
+                        trash_now = ui
+                            .add(
+                                egui::Button::new("Move to trash")
+                                    .fill(Color32::from_rgb(125, 0, 0)),
+                            )
+                            .clicked();
+                        if trashing {
+                            ui.spinner();
+                            ui.label("Moving to trash...");
+                        }
+                    });
+                });
+            });
+        self.cleanup_open = open;
+
+        if mode_changed && self.cleanup_mode == cleanup::CleanupMode::Subfolder {
+            self.cleanup_subfolders = cleanup::subfolders(Path::new(&self.dl_dir));
+        }
+        if changed {
+            self.cleanup_plan = None;
+            self.cleanup_plan_rx = None;
+            self.cleanup_confirm = false;
+        }
+        if preview {
+            let options = cleanup::CleanupOptions {
+                mode: self.cleanup_mode,
+                days: self.cleanup_days,
+                subfolder: self.cleanup_subfolder.clone(),
+                tag_rule: self.cleanup_tag_rule.clone(),
+            };
+            self.cleanup_confirm = false;
+            self.cleanup_plan = None;
+            let (tx, rx) = std::sync::mpsc::channel();
+            let dir = PathBuf::from(&self.dl_dir);
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let _ = tx.send(cleanup::plan(&dir, &options));
+                ctx.request_repaint();
+            });
+            self.cleanup_plan_rx = Some(rx);
+        }
+        if let Some(rx) = &self.cleanup_plan_rx {
+            match rx.try_recv() {
+                Ok(Ok(plan)) => {
+                    self.cleanup_plan = Some(plan);
+                    self.cleanup_plan_rx = None;
+                }
+                Ok(Err(error)) => {
+                    self.cleanup_plan_rx = None;
+                    self.toast(error, ToastKind::Error);
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => {}
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => self.cleanup_plan_rx = None,
+            }
+        }
+        if trash_now {
+            let Some(plan) = self.cleanup_plan.take() else {
+                return;
+            };
+            self.cleanup_confirm = false;
+            let description = format!("{} in {}", self.cleanup_mode.label(), self.dl_dir);
+            self.spawn_trash(TrashOrigin::Cleanup, plan.targets, description, ctx.clone());
+        }
+    }
+
+    /// "Undo last cleanup" plus the list of earlier cleanups that can be restored.
+    fn cleanup_history_ui(&mut self, ui: &mut egui::Ui) {
+        let mut restore = None;
+        if !self.cleanup_history.is_empty() && ui.button("Undo last cleanup").clicked() {
+            restore = Some(self.cleanup_history.len() - 1);
+        }
+        egui::CollapsingHeader::new(format!("Cleanup history ({})", self.cleanup_history.len()))
+            .id_salt("cleanup_history")
+            .show(ui, |ui| {
+                if self.cleanup_history.is_empty() {
+                    ui.label(RichText::new("Nothing trashed from here yet.").weak());
+                }
+                for (i, record) in self.cleanup_history.iter().enumerate().rev() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("Restore").clicked() {
+                            restore = Some(i);
+                        }
+                        ui.label(format!(
+                            "{} ago: {} ({} items)",
+                            format_age(record.time),
+                            record.description,
+                            record.paths.len()
+                        ))
+                        .on_hover_text(
+                            record
+                                .paths
+                                .iter()
+                                .map(|path| path.display().to_string())
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        );
+                    });
+                }
+            });
+        if !self.restore_failures.is_empty() {
+            ui.label(
+                RichText::new(format!(
+                    "{} items could not be restored:",
+                    self.restore_failures.len()
+                ))
+                .color(Color32::YELLOW),
+            );
+            for (path, reason) in &self.restore_failures {
+                ui.label(RichText::new(format!("{}: {reason}", path.display())).weak());
+            }
+            if ui.button("Dismiss").clicked() {
+                self.restore_failures.clear();
+            }
+        }
+        if let Some(i) = restore {
+            self.restore_cleanup(i);
+        }
+    }
+
+    /// Restores one cleanup from the trash. Items that fail stay in the history so they
+    /// can be tried again.
+    fn restore_cleanup(&mut self, i: usize) {
+        let record = self.cleanup_history[i].clone();
+        let failed = match cleanup::restore(&record) {
+            Ok(failed) => failed,
+            Err(error) => {
+                self.toast(error, ToastKind::Error);
+                return;
+            }
+        };
+        let restored = record.paths.len() - failed.len();
+        if failed.is_empty() {
+            self.cleanup_history.remove(i);
+            self.toast(format!("Restored {restored} items."), ToastKind::Success);
+        } else {
+            self.cleanup_history[i].paths = failed.iter().map(|(path, _)| path.clone()).collect();
+            self.toast(
+                format!("Restored {restored} items, {} failed.", failed.len()),
+                ToastKind::Warning,
+            );
+        }
+        self.restore_failures = failed;
+        if let Err(error) = cleanup::save_history(&self.cleanup_history) {
+            self.toast(error, ToastKind::Error);
+        }
+    }
+
+    fn pool_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Download Pools");
+        ui.add_space(8.0);
+        ui.label("Pool IDs or URLs (one per line)");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.pool_id)
+                .desired_rows(3)
+                .hint_text("e.g. 12345 or https://e621.net/pools/12345"),
+        );
+        let pool_ids = api::parse_pool_ids(&self.pool_id);
+        if pool_ids.len() > 1 {
+            ui.label(
+                RichText::new(format!(
+                    "{} pools; each goes into its own folder named after the pool.",
+                    pool_ids.len()
+                ))
+                .weak(),
+            );
+        }
         ui.add_space(10.0);
 
         if ui.button("Save settings").clicked() {
             self.save_pool();
         }
 
-        let pool_id: Option<u64> = self.pool_id.trim().parse().ok();
         let busy = self.job.is_some();
-        ui.add_enabled_ui(!busy && pool_id.is_some(), |ui| {
-            if ui.button("Download Pool").clicked() {
-                if let Some(id) = pool_id {
-                    self.start_job(JobKind::Pool(id), "Pool");
+        ui.add_enabled_ui(!busy && !pool_ids.is_empty(), |ui| {
+            let label = if pool_ids.len() > 1 {
+                format!("Download {} Pools", pool_ids.len())
+            } else {
+                "Download Pool".to_owned()
+            };
+            if ui.button(label).clicked() {
+                match pool_ids.as_slice() {
+                    [id] => self.start_job(JobKind::Pool(*id), "Pool"),
+                    ids => self.start_job(JobKind::Pools(ids.to_vec()), "Pools"),
                 }
             }
         });
@@ -911,6 +2472,74 @@ impl App {
             self.stop_button(ui);
         }
 
+        ui.add_space(16.0);
+        ui.separator();
+        ui.label("Search pools");
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut self.pool_search_name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Creator");
+            ui.text_edit_singleline(&mut self.pool_search_creator);
+        });
+        let searching = self.pool_search_rx.is_some();
+        ui.horizontal(|ui| {
+            let has_query = !self.pool_search_name.trim().is_empty()
+                || !self.pool_search_creator.trim().is_empty();
+            ui.add_enabled_ui(!searching && has_query, |ui| {
+                if ui.button("Search").clicked() {
+                    self.spawn_pool_search(ui.ctx().clone());
+                }
+            });
+            if searching {
+                ui.spinner();
+            }
+        });
+        if !self.pool_results.is_empty() {
+            egui::ScrollArea::vertical()
+                .id_salt("pool_results")
+                .max_height(160.0)
+                .show(ui, |ui| {
+                    for (pool, selected) in &mut self.pool_results {
+                        ui.checkbox(
+                            selected,
+                            format!(
+                                "#{} {} ({} posts, by {})",
+                                pool.id,
+                                pool.display_name(),
+                                pool.post_count,
+                                pool.creator_name
+                            ),
+                        );
+                    }
+                });
+            let selected = self
+                .pool_results
+                .iter()
+                .filter(|(_, selected)| *selected)
+                .map(|(pool, _)| pool.id)
+                .collect::<Vec<_>>();
+            ui.add_enabled_ui(!selected.is_empty(), |ui| {
+                if ui
+                    .button(format!("Add {} selected to the list", selected.len()))
+                    .clicked()
+                {
+                    for id in selected {
+                        if !pool_ids.contains(&id) {
+                            if !self.pool_id.trim().is_empty() && !self.pool_id.ends_with('\n') {
+                                self.pool_id.push('\n');
+                            }
+                            self.pool_id.push_str(&id.to_string());
+                        }
+                    }
+                    for (_, selected) in &mut self.pool_results {
+                        *selected = false;
+                    }
+                }
+            });
+        }
+
         ui.add_space(16.0);
         ui.separator();
         ui.label("Package the downloaded pool into an archive (files are numbered so reading order is preserved):");
@@ -932,6 +2561,37 @@ impl App {
                     }
                 });
         });
+        ui.horizontal(|ui| {
+            ui.label("Split");
+            egui::ComboBox::from_id_salt("zip_split")
+                .selected_text(self.zip_split.label())
+                .show_ui(ui, |ui| {
+                    for mode in [SplitMode::None, SplitMode::Pages, SplitMode::Size] {
+                        ui.selectable_value(&mut self.zip_split, mode, mode.label());
+                    }
+                });
+            match self.zip_split {
+                SplitMode::None => {}
+                SplitMode::Pages => {
+                    ui.add(egui::DragValue::new(&mut self.zip_split_pages).range(1..=10_000));
+                }
+                SplitMode::Size => {
+                    ui.add(egui::DragValue::new(&mut self.zip_split_mb).range(1..=100_000));
+                }
+            }
+        });
+        if self.zip_split != SplitMode::None {
+            ui.label(
+                RichText::new(format!(
+                    "Volumes are written as '{} v01.{}', '{} v02.{}', ... next to the download folder.",
+                    self.zip_name.trim(),
+                    archive_format_label(self.zip_format),
+                    self.zip_name.trim(),
+                    archive_format_label(self.zip_format),
+                ))
+                .weak(),
+            );
+        }
         ui.add_enabled_ui(
             self.zip_job.is_none() && !self.zip_name.trim().is_empty(),
             |ui| {
@@ -941,9 +2601,13 @@ impl App {
                         PathBuf::from(&self.dl_dir),
                         self.zip_name.clone(),
                         self.zip_format,
+                        self.archive_split(),
                         tx,
                     );
-                    self.zip_job = Some(ActiveZip { rx });
+                    self.zip_job = Some(ActiveZip {
+                        rx,
+                        status: "Packaging...".to_owned(),
+                    });
                     self.toast(
                         "Packaging archive (requires 7z on PATH)...",
                         ToastKind::Info,
@@ -951,58 +2615,649 @@ impl App {
                 }
             },
         );
-        if self.zip_job.is_some() {
+        if let Some(zip_job) = &self.zip_job {
             ui.horizontal(|ui| {
                 ui.spinner();
-                ui.label("Packaging...");
+                ui.label(&zip_job.status);
             });
         }
     }
 
-    fn utilities_ui(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Utilities");
+    fn set_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Download a Set");
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            ui.label("Set ID or shortname");
+            ui.add(egui::TextEdit::singleline(&mut self.set_id).hint_text("e.g. 1234 or my_set"));
+        });
+        ui.checkbox(
+            &mut self.set_ordered,
+            "Number files in set order (like a pool)",
+        );
         ui.add_space(10.0);
 
-        if ui.button(format!("Open {} folder", self.dl_dir)).clicked() {
-            if Path::new(&self.dl_dir).exists() {
-                open_dl_dir(&self.dl_dir);
-            } else {
-                self.toast(
-                    format!("No {} folder found.", self.dl_dir),
-                    ToastKind::Error,
+        if ui.button("Save settings").clicked() {
+            self.save_set();
+        }
+
+        let busy = self.job.is_some();
+        ui.add_enabled_ui(!busy && !self.set_id.trim().is_empty(), |ui| {
+            if ui.button("Download Set").clicked() {
+                self.start_job(
+                    JobKind::Set {
+                        set: self.set_id.trim().to_owned(),
+                        ordered: self.set_ordered,
+                    },
+                    "Set",
                 );
             }
+        });
+        if busy {
+            self.stop_button(ui);
+        }
+    }
+
+    fn popular_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Download Popular Posts");
+        ui.add_space(8.0);
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Popular by");
+            for scale in popular::Scale::ALL {
+                changed |= ui
+                    .radio_value(&mut self.popular_scale, scale, scale.label())
+                    .changed();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("From");
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut self.popular_from)
+                        .desired_width(90.0)
+                        .hint_text("YYYY-MM-DD"),
+                )
+                .changed();
+            ui.label("to");
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut self.popular_to)
+                        .desired_width(90.0)
+                        .hint_text("optional"),
+                )
+                .changed();
+            if ui.button("Today").clicked() {
+                self.popular_from = popular::today();
+                self.popular_to.clear();
+                changed = true;
+            }
+        });
+        if changed {
+            self.popular_dates =
+                popular::dates(&self.popular_from, &self.popular_to, self.popular_scale);
+        }
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.popular_filter)
+                    .hint_text("e.g. rating:s -comic; blank keeps all"),
+            );
+        });
+        match &self.popular_dates {
+            Ok(dates) if dates.len() > 1 => {
+                ui.label(RichText::new(format!("{} listings", dates.len())).weak());
+            }
+            Ok(_) => {}
+            Err(error) => {
+                ui.label(RichText::new(error).small().color(Color32::YELLOW));
+            }
         }
+        ui.label(
+            RichText::new("Posts your account's blacklist hides are skipped when logged in.")
+                .small()
+                .weak(),
+        );
+        ui.add_space(10.0);
 
+        let busy = self.job.is_some();
+        ui.add_enabled_ui(!busy && self.popular_dates.is_ok(), |ui| {
+            if ui.button("Download Popular").clicked() {
+                if let Ok(dates) = self.popular_dates.clone() {
+                    self.start_job(
+                        JobKind::Popular {
+                            scale: self.popular_scale,
+                            dates,
+                            filter: self.popular_filter.trim().to_owned(),
+                        },
+                        "Popular",
+                    );
+                }
+            }
+        });
+        if busy {
+            self.stop_button(ui);
+        }
+    }
+
+    fn artists_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Artist Watchlist");
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            ui.label("Artist tag");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.watchlist_new).hint_text("e.g. some_artist"),
+            );
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let name = self.watchlist_new.trim().to_lowercase().replace(' ', "_");
+            if (ui.button("Add").clicked() || entered) && !name.is_empty() {
+                if !self.gui.watchlist.contains(&name) {
+                    self.gui.watchlist.push(name);
+                    self.save_watchlist();
+                }
+                self.watchlist_new.clear();
+            }
+        });
+        let refreshing = self.artist_rx.is_some();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !refreshing && !self.gui.watchlist.is_empty(),
+                    egui::Button::new("Refresh info"),
+                )
+                .on_hover_text("Fetch artist entries and post counts, and count local files")
+                .clicked()
+            {
+                self.spawn_artist_refresh(ui.ctx().clone());
+            }
+            if refreshing {
+                ui.spinner();
+            }
+        });
         ui.add_space(6.0);
-        let cleanup_style = egui::Button::new(format!("Cleanup (trash {})", self.dl_dir))
-            .fill(Color32::from_rgb(125, 0, 0));
-        if ui.add(cleanup_style).clicked() {
-            if Path::new(&self.dl_dir).exists() {
-                let _ = trash::delete(&self.dl_dir);
-                self.toast("Cleaned up!", ToastKind::Info);
-            } else {
-                self.toast(
-                    format!("No {} folder found.", self.dl_dir),
-                    ToastKind::Error,
-                );
+
+        let busy = self.job.is_some();
+        let mut remove = None;
+        let mut download = None;
+        egui::ScrollArea::vertical()
+            .id_salt("watchlist")
+            .max_height(360.0)
+            .show(ui, |ui| {
+                for (i, name) in self.gui.watchlist.iter().enumerate() {
+                    let status = self
+                        .artist_status
+                        .iter()
+                        .find(|status| &status.name == name);
+                    ui.horizontal(|ui| {
+                        ui.strong(name);
+                        if let Some(status) = status {
+                            let counts = match status.available {
+                                Some(available) => {
+                                    format!("{} local / {available} available", status.local)
+                                }
+                                None => format!("{} local", status.local),
+                            };
+                            ui.label(RichText::new(counts).weak());
+                        }
+                        if ui
+                            .add_enabled(!busy, egui::Button::new("Download").small())
+                            .clicked()
+                        {
+                            download = Some(vec![name.clone()]);
+                        }
+                        if ui.small_button("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                    let Some(status) = status else {
+                        continue;
+                    };
+                    if let Some(entry) = &status.entry {
+                        if !entry.other_names.is_empty() {
+                            ui.label(
+                                RichText::new(format!("Also: {}", entry.other_names.join(", ")))
+                                    .small(),
+                            );
+                        }
+                        for url in &entry.urls {
+                            let text = RichText::new(&url.url).small();
+                            let text = if url.is_active { text } else { text.weak() };
+                            ui.hyperlink_to(text, &url.url);
+                        }
+                    }
+                    if let Some(error) = &status.error {
+                        ui.label(RichText::new(error).small().color(Color32::YELLOW));
+                    }
+                    ui.separator();
+                }
+            });
+        if let Some(i) = remove {
+            self.gui.watchlist.remove(i);
+            self.save_watchlist();
+        }
+        ui.add_space(10.0);
+
+        ui.add_enabled_ui(!busy && !self.gui.watchlist.is_empty(), |ui| {
+            if ui
+                .button("Download new posts of all")
+                .on_hover_text(
+                    "Fetches each artist's posts newer than their last complete download, \
+                     and older posts an earlier page limit left out up to Pages more; \
+                     posts already in the track file are skipped",
+                )
+                .clicked()
+            {
+                download = Some(self.gui.watchlist.clone());
             }
+        });
+        if let Some(names) = download {
+            self.start_job(JobKind::Artists(names), "Artists");
+        }
+        if busy {
+            self.stop_button(ui);
         }
+    }
 
+    fn posts_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Download Posts");
         ui.add_space(8.0);
-        ui.add_enabled_ui(self.job.is_none(), |ui| {
-            if ui.button("Retry failed downloads").clicked() {
-                self.start_job(JobKind::RetryFailed, "Retry failed");
+        ui.label("Post IDs, post URLs or md5s (one per line)");
+        egui::ScrollArea::vertical()
+            .id_salt("post_list")
+            .max_height(240.0)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.post_list)
+                        .desired_rows(8)
+                        .desired_width(f32::INFINITY)
+                        .hint_text("https://e621.net/posts/12345"),
+                );
+            });
+        let refs = api::parse_post_refs(&self.post_list);
+        let mut summary = format!("{} ids, {} md5s", refs.ids.len(), refs.md5s.len());
+        if refs.unrecognised > 0 {
+            summary.push_str(&format!(", {} lines not recognised", refs.unrecognised));
+        }
+        ui.label(RichText::new(summary).weak());
+        ui.add_space(10.0);
+
+        ui.add_space(6.0);
+        ui.horizontal(|ui| {
+            ui.label("Import file");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.import_path)
+                    .hint_text(".txt, .csv or manifest .json"),
+            );
+            if ui.button("Import").clicked() {
+                let path = PathBuf::from(self.import_path.trim());
+                self.import_post_files(&[path]);
             }
         });
+        ui.label(RichText::new("You can also drop files onto the window to import them.").weak());
+        self.import_preview_ui(ui);
+        ui.add_space(10.0);
 
-        ui.add_space(16.0);
+        let busy = self.job.is_some();
+        let has_posts = !refs.ids.is_empty() || !refs.md5s.is_empty();
+        ui.add_enabled_ui(!busy && has_posts, |ui| {
+            if ui.button("Download Posts").clicked() {
+                self.start_job(
+                    JobKind::Posts {
+                        ids: refs.ids,
+                        md5s: refs.md5s,
+                    },
+                    "Posts",
+                );
+            }
+        });
+        if busy {
+            self.stop_button(ui);
+        }
+    }
+
+    fn import_preview_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(preview) = &self.import_preview else {
+            return;
+        };
+        let mut add_to_list = false;
+        let mut download = false;
+        let mut dismiss = false;
+        ui.group(|ui| {
+            ui.label(RichText::new(format!("Imported {}", preview.source)).strong());
+            let mut summary = format!(
+                "{} ids and {} md5s parsed, {} already in the tracker.",
+                preview.refs.ids.len(),
+                preview.refs.md5s.len(),
+                preview.tracked
+            );
+            if preview.refs.unrecognised > 0 {
+                summary.push_str(&format!(
+                    " {} entries not recognised.",
+                    preview.refs.unrecognised
+                ));
+            }
+            ui.label(summary);
+            ui.horizontal(|ui| {
+                add_to_list = ui.button("Add to list").clicked();
+                ui.add_enabled_ui(self.job.is_none(), |ui| {
+                    download = ui.button("Download now").clicked();
+                });
+                dismiss = ui.button("Dismiss").clicked();
+            });
+        });
+        if add_to_list || download || dismiss {
+            let Some(preview) = self.import_preview.take() else {
+                return;
+            };
+            if add_to_list {
+                for entry in preview
+                    .refs
+                    .ids
+                    .iter()
+                    .map(u64::to_string)
+                    .chain(preview.refs.md5s.iter().cloned())
+                {
+                    if !self.post_list.is_empty() && !self.post_list.ends_with('\n') {
+                        self.post_list.push('\n');
+                    }
+                    self.post_list.push_str(&entry);
+                }
+            } else if download {
+                self.start_job(
+                    JobKind::Posts {
+                        ids: preview.refs.ids,
+                        md5s: preview.refs.md5s,
+                    },
+                    "Posts",
+                );
+            }
+        }
+    }
+
+    fn utilities_ui(&mut self, ui: &mut egui::Ui) {
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.heading("Utilities");
+                ui.add_space(10.0);
+
+                if ui.button(format!("Open {} folder", self.dl_dir)).clicked() {
+                    if Path::new(&self.dl_dir).exists() {
+                        open_dl_dir(&self.dl_dir);
+                    } else {
+                        self.toast(
+                            format!("No {} folder found.", self.dl_dir),
+                            ToastKind::Error,
+                        );
+                    }
+                }
+
+                ui.add_space(6.0);
+                let cleanup_style = egui::Button::new(format!("Cleanup {}...", self.dl_dir))
+                    .fill(Color32::from_rgb(125, 0, 0));
+                if ui.add(cleanup_style).clicked() {
+                    if Path::new(&self.dl_dir).exists() {
+                        self.cleanup_open = true;
+                        self.cleanup_plan = None;
+                        self.cleanup_confirm = false;
+                        self.cleanup_subfolders = cleanup::subfolders(Path::new(&self.dl_dir));
+                    } else {
+                        self.toast(
+                            format!("No {} folder found.", self.dl_dir),
+                            ToastKind::Error,
+                        );
+                    }
+                }
+
+                self.cleanup_history_ui(ui);
+
+                ui.add_space(8.0);
+                ui.add_enabled_ui(self.job.is_none(), |ui| {
+                    if ui.button("Retry failed downloads").clicked() {
+                        self.start_job(JobKind::RetryFailed, "Retry failed");
+                    }
+                });
+
+                ui.add_space(16.0);
+                ui.separator();
+                self.duplicate_index_ui(ui);
+
+                ui.add_space(16.0);
+                ui.separator();
+                self.tag_db_ui(ui);
+
+                ui.add_space(16.0);
+                ui.separator();
+                self.audit_ui(ui);
+
+                ui.add_space(16.0);
+                ui.label(
+                    RichText::new(
+                        "The API key is read from a plain-text file called 'key' next to the executable.",
+                    )
+                    .weak(),
+                );
+            });
+    }
+
+    fn tag_db_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Tag database (autocomplete)");
+        ui.label(
+            RichText::new(format!(
+                "{} tags, {} aliases, {} tags with implications",
+                self.tag_assist.db.len(),
+                self.tag_assist.db.aliases.len(),
+                self.tag_assist.db.implications.len()
+            ))
+            .weak(),
+        );
+        ui.label("e621 dump files (tags, tag_aliases, tag_implications; .gz ok; one per line)");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.tag_dump_paths)
+                .desired_rows(2)
+                .hint_text("e.g. ./tags-2024-01-01.csv.gz"),
+        );
+        ui.horizontal(|ui| {
+            let importing = self.tag_db_rx.is_some();
+            if ui
+                .add_enabled(!importing, egui::Button::new("Import dumps"))
+                .clicked()
+            {
+                let dumps: Vec<PathBuf> = self
+                    .tag_dump_paths
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from)
+                    .collect();
+                if dumps.is_empty() {
+                    self.toast("Enter at least one dump file.", ToastKind::Warning);
+                } else {
+                    self.spawn_tag_db_load(ui.ctx().clone(), dumps);
+                }
+            }
+            if importing {
+                ui.spinner();
+            }
+        });
+        ui.checkbox(
+            &mut self.tag_assist.live_lookup,
+            "Also ask the site for suggestions while typing",
+        );
+    }
+
+    fn duplicate_index_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Duplicate index");
+        ui.label(RichText::new(self.duplicate_index_path().display().to_string()).weak());
+        if ui.button("Show stats").clicked() {
+            self.show_index_stats();
+        }
+        if let Some(stats) = &self.index_stats {
+            ui.label(stats);
+        }
+
+        let idle = self.job.is_none();
+        ui.add_space(6.0);
+        ui.label("Folders to scan (one per line)");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.index_folders)
+                .desired_rows(2)
+                .hint_text(self.dl_dir.as_str()),
+        );
+        ui.add_enabled_ui(idle, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                if ui.button("Rebuild from folders").clicked() {
+                    let folders = self.index_scan_folders();
+                    self.start_index_task(library::IndexTask::Rebuild(folders), "Rebuild index");
+                }
+                if ui
+                    .button("Import library")
+                    .on_hover_text("Add the folders' files to the index, keeping existing entries")
+                    .clicked()
+                {
+                    let folders = self.index_scan_folders();
+                    self.start_index_task(library::IndexTask::Import(folders), "Import library");
+                }
+                if ui.button("Prune missing").clicked() {
+                    self.start_index_task(library::IndexTask::Prune, "Prune index");
+                }
+            });
+            ui.horizontal_wrapped(|ui| {
+                if ui
+                    .button("Build perceptual index")
+                    .on_hover_text("Hash the folders' images for near-duplicate detection")
+                    .clicked()
+                {
+                    let folders = self.index_scan_folders();
+                    self.start_index_task(
+                        library::IndexTask::BuildPerceptual(folders),
+                        "Perceptual index",
+                    );
+                }
+                if ui.button("Find similar images").clicked() {
+                    self.similar_groups.clear();
+                    self.start_index_task(
+                        library::IndexTask::SimilarReport(self.near_duplicate_threshold),
+                        "Similar images",
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.index_merge_path)
+                        .hint_text("other .e-cli-md5.json"),
+                );
+                let has_path = !self.index_merge_path.trim().is_empty();
+                if ui
+                    .add_enabled(has_path, egui::Button::new("Merge"))
+                    .clicked()
+                {
+                    let other = PathBuf::from(self.index_merge_path.trim());
+                    self.start_index_task(library::IndexTask::Merge(other), "Merge index");
+                }
+            });
+        });
+        if !idle {
+            self.stop_button(ui);
+        }
+        self.similar_groups_ui(ui);
+    }
+
+    fn audit_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Library audit");
         ui.label(
             RichText::new(
-                "The API key is read from a plain-text file called 'key' next to the executable.",
+                "Checks files against the duplicate index, the download manifest and their md5 file names.",
             )
             .weak(),
         );
+        ui.add_enabled_ui(self.job.is_none(), |ui| {
+            if ui.button(format!("Audit {}", self.dl_dir)).clicked() {
+                self.start_audit();
+            }
+        });
+        ui.add_space(6.0);
+        ui.add_enabled_ui(
+            self.job.is_none() && !self.track_file.trim().is_empty(),
+            |ui| {
+                if ui
+                    .button("Check for replacements")
+                    .on_hover_text(
+                        "Re-fetch tracked posts and download files that were replaced on the site",
+                    )
+                    .clicked()
+                {
+                    self.start_replacement_check();
+                }
+            },
+        );
+        ui.checkbox(
+            &mut self.archive_replaced,
+            "Move replaced files into 'replaced/' (otherwise keep them in place)",
+        );
+        if self.audit_issues.is_empty() {
+            return;
+        }
+        let repairable = self
+            .audit_issues
+            .iter()
+            .filter(|issue| issue.post_id.is_some() || issue.md5.is_some())
+            .count();
+        ui.horizontal(|ui| {
+            ui.label(format!("{} problems found", self.audit_issues.len()));
+            ui.add_enabled_ui(self.job.is_none() && repairable > 0, |ui| {
+                if ui
+                    .button(format!("Re-download {repairable}"))
+                    .on_hover_text(
+                        "A broken file is moved to the trash only once its new download \
+                         matches the post",
+                    )
+                    .clicked()
+                {
+                    self.repair_audit_issues();
+                }
+            });
+            if ui.button("Clear").clicked() {
+                self.audit_issues.clear();
+            }
+        });
+        egui::ScrollArea::vertical()
+            .id_salt("audit_issues")
+            .max_height(160.0)
+            .show(ui, |ui| {
+                for issue in &self.audit_issues {
+                    ui.label(format!("{}: {}", issue.kind.label(), issue.path.display()));
+                }
+            });
+    }
+
+    fn similar_groups_ui(&mut self, ui: &mut egui::Ui) {
+        if self.similar_groups.is_empty() {
+            return;
+        }
+        ui.add_space(6.0);
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{} groups of similar images",
+                self.similar_groups.len()
+            ));
+            if ui.button("Clear").clicked() {
+                self.similar_groups.clear();
+            }
+        });
+        for (i, group) in self.similar_groups.iter().enumerate() {
+            egui::CollapsingHeader::new(format!("Group {} ({} files)", i + 1, group.len()))
+                .id_salt(("similar_group", i))
+                .show(ui, |ui| {
+                    for file in group {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("Open").clicked() {
+                                open_dl_dir(&file.to_string_lossy());
+                            }
+                            ui.label(file.display().to_string());
+                        });
+                    }
+                });
+        }
     }
 
     fn stop_button(&mut self, ui: &mut egui::Ui) {
@@ -1090,34 +3345,288 @@ impl App {
     }
 }
 
-fn tags_edit(ui: &mut egui::Ui, tags: &mut String) {
+/// The tags field, with suggestions for the tag under the cursor. Returns a prefix to
+/// look up on the site when live lookups are on and it hasn't been asked for yet.
+fn tags_edit(ui: &mut egui::Ui, tags: &mut String, assist: &mut TagAssist) -> Option<String> {
     ui.label("Tags");
-    ui.add(
-        egui::TextEdit::multiline(tags)
-            .desired_rows(2)
-            .char_limit(250),
-    );
+    let mut layouter = |ui: &egui::Ui, text: &dyn egui::TextBuffer, wrap_width: f32| {
+        let mut job = highlight_query(ui, text.as_str(), &assist.db);
+        job.wrap.max_width = wrap_width;
+        ui.fonts_mut(|fonts| fonts.layout_job(job))
+    };
+    let mut output = egui::TextEdit::multiline(tags)
+        .desired_rows(2)
+        .char_limit(250)
+        .layouter(&mut layouter)
+        .show(ui);
+    let rect_id = output.response.id.with("suggestions");
+    let cursor = output.cursor_range.map(|range| range.primary.index)?;
+    let chars: Vec<char> = tags.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let start = chars[..cursor]
+        .iter()
+        .rposition(|c| c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let end = chars[cursor..]
+        .iter()
+        .position(|c| c.is_whitespace())
+        .map_or(chars.len(), |i| cursor + i);
+    // Keep the `-`/`~` prefix; metatags like `rating:s` get no suggestions.
+    let prefixed = chars[start..cursor]
+        .iter()
+        .take_while(|c| matches!(c, '-' | '~'))
+        .count();
+    let word: String = chars[start + prefixed..cursor]
+        .iter()
+        .collect::<String>()
+        .to_lowercase();
+
+    let hovered = ui
+        .data(|data| data.get_temp::<egui::Rect>(rect_id))
+        .is_some_and(|rect| ui.rect_contains_pointer(rect));
+    if word.is_empty() || word.contains(':') || !(output.response.has_focus() || hovered) {
+        ui.data_mut(|data| data.remove::<egui::Rect>(rect_id));
+        return None;
+    }
+
+    let suggestions = assist.suggest(&word);
+    let mut chosen = None;
+    if !suggestions.is_empty() {
+        let frame = ui.group(|ui| {
+            for suggestion in suggestions {
+                let name = suggestion.tag.name.replace('_', " ");
+                let text = match &suggestion.alias {
+                    Some(alias) => format!("{} \u{2192} {name}", alias.replace('_', " ")),
+                    None => name,
+                };
+                ui.horizontal(|ui| {
+                    let label = RichText::new(text).color(category_color(suggestion.tag.category));
+                    if ui.add(egui::Button::new(label).frame(false)).clicked() {
+                        chosen = Some(suggestion.tag.name.clone());
+                    }
+                    ui.label(RichText::new(format_count(suggestion.tag.post_count)).weak());
+                });
+            }
+        });
+        ui.data_mut(|data| data.insert_temp(rect_id, frame.response.rect));
+    }
+
+    if let Some(name) = chosen {
+        let before: String = chars[..start + prefixed].iter().collect();
+        let after: String = chars[end..].iter().collect();
+        let after = after.trim_start();
+        *tags = format!("{before}{name} {after}");
+        let position = before.chars().count() + name.chars().count() + 1;
+        output
+            .state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(
+                egui::text::CCursor::new(position),
+            )));
+        output.state.store(ui.ctx(), output.response.id);
+        output.response.request_focus();
+        return None;
+    }
+
+    let looked_up = assist.live.as_ref().map(|(prefix, _)| prefix.as_str());
+    (assist.live_lookup && word.chars().count() >= 3 && looked_up != Some(word.as_str()))
+        .then_some(word)
 }
 
-fn format_bytes(bytes: f64) -> String {
-    const MB: f64 = 1024.0 * 1024.0;
-    if bytes >= MB {
-        format!("{:.2} MB", bytes / MB)
-    } else if bytes >= 1024.0 {
-        format!("{:.2} KB", bytes / 1024.0)
-    } else {
-        format!("{bytes:.0} bytes")
+/// Colours each query token: tags by category when the tag database knows them,
+/// metatags (red when unknown), `-`/`~` prefixes and group parentheses.
+fn highlight_query(ui: &egui::Ui, query: &str, db: &tagdb::TagDb) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let plain = ui.visuals().text_color();
+    let mut job = egui::text::LayoutJob::default();
+    let append = |job: &mut egui::text::LayoutJob, text: &str, color: Color32| {
+        job.append(
+            text,
+            0.0,
+            egui::TextFormat {
+                font_id: font_id.clone(),
+                color,
+                ..Default::default()
+            },
+        );
+    };
+    let mut written = 0;
+    for token in query::tokenize(query) {
+        append(&mut job, &query[written..token.start], plain);
+        let text = &query[token.start..token.end];
+        let prefix_len = text.len() - text.trim_start_matches(['-', '~']).len();
+        let prefix_color = if token.negated {
+            Color32::from_rgb(0xff, 0x3d, 0x3d)
+        } else {
+            Color32::YELLOW
+        };
+        append(&mut job, &text[..prefix_len], prefix_color);
+        let color = match token.kind {
+            query::TokenKind::GroupOpen | query::TokenKind::GroupClose => {
+                ui.visuals().weak_text_color()
+            }
+            query::TokenKind::Metatag { known: true } => Color32::from_rgb(0x7f, 0xb3, 0xff),
+            query::TokenKind::Metatag { known: false } => Color32::from_rgb(0xff, 0x3d, 0x3d),
+            query::TokenKind::Tag => {
+                let canonical = db.aliases.get(&token.name).unwrap_or(&token.name);
+                db.get(canonical)
+                    .map_or(plain, |tag| category_color(tag.category))
+            }
+        };
+        append(&mut job, &text[prefix_len..], color);
+        written = token.end;
     }
+    append(&mut job, &query[written..], plain);
+    job
+}
+
+/// Optional min/max bounds, each enabled by its own checkbox.
+fn range_edit(ui: &mut egui::Ui, (min, max): &mut (Option<i64>, Option<i64>)) {
+    ui.horizontal(|ui| {
+        for (label, bound) in [("min", min), ("max", max)] {
+            let mut enabled = bound.is_some();
+            if ui.checkbox(&mut enabled, label).changed() {
+                *bound = enabled.then_some(0);
+            }
+            if let Some(value) = bound {
+                ui.add(egui::DragValue::new(value));
+            }
+        }
+    });
+}
+
+/// A combo box over `values` with an "Any" entry for `None`.
+fn option_combo(ui: &mut egui::Ui, id: &str, value: &mut Option<String>, values: &[&str]) {
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(value.as_deref().unwrap_or("Any"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(value, None, "Any");
+            for option in values {
+                ui.selectable_value(value, Some((*option).to_owned()), *option);
+            }
+        });
+}
+
+/// A sort order selector over [`query::ORDERS`]; `None` is the site's default.
+fn order_combo(ui: &mut egui::Ui, id: &str, order: &mut Option<String>) {
+    ui.horizontal(|ui| {
+        ui.label("Sort");
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(order.as_deref().map_or("Default", query::order_label))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(order, None, "Default");
+                for (value, label) in query::ORDERS {
+                    ui.selectable_value(order, Some((*value).to_owned()), *label)
+                        .on_hover_text(format!("order:{value}"));
+                }
+            });
+    });
 }
 
-fn format_duration(duration: Duration) -> String {
-    let seconds = duration.as_secs();
-    if seconds >= 3600 {
-        format!("{}h {:02}m", seconds / 3600, (seconds / 60) % 60)
-    } else if seconds >= 60 {
-        format!("{}m {:02}s", seconds / 60, seconds % 60)
+/// The Pool tab's text for a saved pool: the full list from gui.toml when it starts with
+/// the id e-cli's config kept, otherwise just that id.
+fn pool_list(first: u64, pools: &[u64]) -> String {
+    if pools.first() == Some(&first) {
+        pools
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
     } else {
-        format!("{}s", seconds)
+        first.to_string()
+    }
+}
+
+/// Applies e-cli's Random setting to a tab's sort order.
+fn apply_random(order: &mut Option<String>, random: bool) {
+    if random {
+        *order = Some("random".to_owned());
+    } else if order.as_deref() == Some("random") {
+        *order = None;
+    }
+}
+
+/// A preset's sort order; presets saved before orders existed only know Random.
+fn preset_order(random: Option<bool>, gui_preset: &settings::GuiPreset) -> Option<String> {
+    gui_preset
+        .order
+        .clone()
+        .or_else(|| (random == Some(true)).then(|| "random".to_owned()))
+}
+
+/// Lists what [`query::check`] found wrong with a query, if anything.
+fn query_warnings(
+    ui: &mut egui::Ui,
+    query: &str,
+    order: Option<&str>,
+    implicit: usize,
+    db: &tagdb::TagDb,
+) {
+    for warning in query::check(query, order, implicit, db) {
+        ui.label(RichText::new(warning).small().color(Color32::YELLOW));
+    }
+}
+
+/// e621's colours for tag categories.
+fn category_color(category: u8) -> Color32 {
+    match category {
+        1 => Color32::from_rgb(0xf2, 0xac, 0x08),
+        2 => Color32::from_rgb(0xc0, 0xc0, 0xc0),
+        3 => Color32::from_rgb(0xdd, 0x00, 0xdd),
+        4 => Color32::from_rgb(0x00, 0xaa, 0x00),
+        5 => Color32::from_rgb(0xed, 0x5d, 0x1f),
+        6 => Color32::from_rgb(0xff, 0x3d, 0x3d),
+        7 => Color32::from_rgb(0xff, 0xff, 0xff),
+        8 => Color32::from_rgb(0x22, 0x88, 0x22),
+        _ => Color32::from_rgb(0xb4, 0xc7, 0xd9),
+    }
+}
+
+/// Post counts as the site shows them, e.g. "12.3k".
+fn format_count(count: u64) -> String {
+    match count {
+        1_000_000.. => format!("{:.1}M", count as f64 / 1_000_000.0),
+        1_000.. => format!("{:.1}k", count as f64 / 1_000.0),
+        _ => count.to_string(),
+    }
+}
+
+/// Combined size of `files`; missing files count as empty.
+fn total_size(files: &[PathBuf]) -> u64 {
+    files
+        .iter()
+        .filter_map(|file| file.metadata().ok())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+fn mirror_checkbox(ui: &mut egui::Ui, mirror: &mut bool, pages: i64) {
+    ui.add_enabled(
+        pages == -1,
+        egui::Checkbox::new(
+            mirror,
+            "Mirror (report files this query downloaded that left the result)",
+        ),
+    )
+    .on_hover_text(
+        "Only files downloaded by this query with Mirror on are tracked, so other jobs \
+         sharing the folder are never reported.",
+    )
+    .on_disabled_hover_text("Mirroring needs every page: set Pages to -1 in Config.");
+}
+
+/// How long ago a UNIX timestamp was, e.g. "3 h".
+fn format_age(time: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(time);
+    let secs = now.saturating_sub(time);
+    match secs {
+        0..60 => format!("{secs} s"),
+        60..3600 => format!("{} min", secs / 60),
+        3600..86400 => format!("{} h", secs / 3600),
+        _ => format!("{} days", secs / 86400),
     }
 }
 
@@ -0,0 +1,232 @@
+//! Perceptual (difference) hashes for spotting re-uploads at other resolutions or
+//! compression levels, which the MD5 duplicate index can't catch. The index lives next to
+//! the duplicate index as `.e-cli-phash.json`, keyed by file md5.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+const INDEX_FILE: &str = ".e-cli-phash.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PerceptualEntry {
+    pub hash: u64,
+    /// Local file the hash came from; posts hashed from their preview before download
+    /// have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct PerceptualIndex {
+    pub entries: BTreeMap<String, PerceptualEntry>,
+}
+
+/// The perceptual index sits beside the duplicate index at `duplicate_index`.
+pub fn index_path(duplicate_index: &Path) -> PathBuf {
+    duplicate_index.with_file_name(INDEX_FILE)
+}
+
+impl PerceptualIndex {
+    /// Loads an index, treating a missing file as empty.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|error| format!("Invalid perceptual index {}: {error}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(format!("Could not read {}: {error}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, text)
+            .map_err(|error| format!("Could not write {}: {error}", path.display()))
+    }
+}
+
+/// Number of differing bits between two hashes.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// 64-bit difference hash: shrink to 9x8 greyscale and compare horizontal neighbours.
+pub fn dhash(image: &image::DynamicImage) -> u64 {
+    let small = image
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub fn hash_file(path: &Path) -> Option<u64> {
+    image::open(path).ok().map(|image| dhash(&image))
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> Option<u64> {
+    image::load_from_memory(bytes)
+        .ok()
+        .map(|image| dhash(&image))
+}
+
+/// e621 serves a small JPEG preview for every post at a path derived from its md5.
+pub fn preview_url(md5: &str) -> Option<String> {
+    (md5.len() == 32).then(|| {
+        format!(
+            "https://static1.e621.net/data/preview/{}/{}/{md5}.jpg",
+            &md5[..2],
+            &md5[2..4]
+        )
+    })
+}
+
+/// Fetches a post's preview and hashes it, so near-duplicates can be skipped before the
+/// full file is downloaded.
+pub fn hash_preview(client: &reqwest::blocking::Client, md5: &str) -> Option<u64> {
+    let bytes = client
+        .get(preview_url(md5)?)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.bytes())
+        .ok()?;
+    hash_bytes(&bytes)
+}
+
+/// Groups local files whose hashes are within `threshold` bits of each other. Only groups
+/// with more than one file are returned, largest first; `None` when cancelled.
+pub fn similar_groups(
+    index: &PerceptualIndex,
+    threshold: u32,
+    cancel: &AtomicBool,
+) -> Option<Vec<Vec<PathBuf>>> {
+    let files: Vec<(u64, &PathBuf)> = index
+        .entries
+        .values()
+        .filter_map(|entry| Some((entry.hash, entry.path.as_ref()?)))
+        .collect();
+    let mut tree = BkTree::default();
+    for (i, (hash, _)) in files.iter().enumerate() {
+        tree.insert(*hash, i);
+    }
+
+    // Union-find over every pair the tree finds close enough.
+    let mut parent: Vec<usize> = (0..files.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut close = Vec::new();
+    for (i, (hash, _)) in files.iter().enumerate() {
+        if i % 1024 == 0 && cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        close.clear();
+        tree.find(*hash, threshold, &mut close);
+        for &j in &close {
+            let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+            parent[a] = b;
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<PathBuf>> = BTreeMap::new();
+    for (i, (_, path)) in files.iter().enumerate() {
+        groups
+            .entry(root(&mut parent, i))
+            .or_default()
+            .push((*path).clone());
+    }
+    let mut groups: Vec<Vec<PathBuf>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+    Some(groups)
+}
+
+/// A BK-tree over hashes by bit distance, holding caller-chosen item numbers. A search
+/// only descends into children whose edge distance is within `threshold` of the query's
+/// distance to the node, so small thresholds touch a small part of the tree.
+#[derive(Default)]
+pub struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    hash: u64,
+    /// Items with exactly this hash.
+    items: Vec<usize>,
+    /// (distance to this node, child node index).
+    children: Vec<(u32, usize)>,
+}
+
+impl BkTree {
+    pub fn insert(&mut self, hash: u64, item: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode {
+                hash,
+                items: vec![item],
+                children: Vec::new(),
+            });
+            return;
+        }
+        let mut node = 0;
+        loop {
+            let bits = distance(hash, self.nodes[node].hash);
+            if bits == 0 {
+                self.nodes[node].items.push(item);
+                return;
+            }
+            match self.nodes[node]
+                .children
+                .iter()
+                .find(|(edge, _)| *edge == bits)
+            {
+                Some(&(_, child)) => node = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(BkNode {
+                        hash,
+                        items: vec![item],
+                        children: Vec::new(),
+                    });
+                    self.nodes[node].children.push((bits, child));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Pushes every item within `threshold` bits of `hash` onto `found`.
+    pub fn find(&self, hash: u64, threshold: u32, found: &mut Vec<usize>) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let bits = distance(hash, node.hash);
+            if bits <= threshold {
+                found.extend(&node.items);
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|(edge, _)| edge.abs_diff(bits) <= threshold)
+                    .map(|(_, child)| *child),
+            );
+        }
+    }
+}
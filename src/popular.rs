@@ -0,0 +1,273 @@
+//! e621's popular posts by day, week or month, and the calendar arithmetic needed to walk
+//! a date range of them without pulling in a date crate.
+
+/// The period one popular listing covers.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    Day,
+    Week,
+    Month,
+}
+
+impl Scale {
+    pub const ALL: [Scale; 3] = [Scale::Day, Scale::Week, Scale::Month];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Scale::Day => "Day",
+            Scale::Week => "Week",
+            Scale::Month => "Month",
+        }
+    }
+
+    /// The `scale` parameter of `popular.json`.
+    pub fn param(self) -> &'static str {
+        match self {
+            Scale::Day => "day",
+            Scale::Week => "week",
+            Scale::Month => "month",
+        }
+    }
+}
+
+/// An account blacklist: a post is hidden when every tag of one rule matches it. A rule
+/// is a line of tags, `-tag` exclusions, `~tag` alternatives and `rating:` filters; lines
+/// with other metatags can't be checked here and are kept aside in `ignored`.
+#[derive(Default)]
+pub struct Blacklist {
+    rules: Vec<Vec<String>>,
+    /// Lines left out because they use metatags other than `rating:`.
+    pub ignored: Vec<String>,
+}
+
+impl Blacklist {
+    pub fn parse(text: &str) -> Blacklist {
+        let mut blacklist = Blacklist::default();
+        for line in text.lines() {
+            let rule: Vec<String> = line.split_whitespace().map(str::to_lowercase).collect();
+            if rule.is_empty() {
+                continue;
+            }
+            let supported = rule.iter().all(|tag| {
+                let tag = tag.trim_start_matches(['-', '~']);
+                !tag.contains(':') || tag.starts_with("rating:")
+            });
+            if supported {
+                blacklist.rules.push(rule);
+            } else {
+                blacklist.ignored.push(line.trim().to_owned());
+            }
+        }
+        blacklist
+    }
+
+    /// Whether a post with these tags and rating (`s`, `q` or `e`) is hidden.
+    pub fn hides(&self, tags: &[&str], rating: &str) -> bool {
+        let has = |tag: &str| match tag.strip_prefix("rating:") {
+            Some(wanted) => wanted.starts_with(rating) && !rating.is_empty(),
+            None => tags
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(tag)),
+        };
+        self.rules.iter().any(|rule| {
+            let mut any_of = rule
+                .iter()
+                .filter_map(|tag| tag.strip_prefix('~'))
+                .peekable();
+            let any_of_matches = any_of.peek().is_none() || any_of.any(has);
+            any_of_matches
+                && rule.iter().all(|tag| {
+                    if let Some(excluded) = tag.strip_prefix('-') {
+                        !has(excluded)
+                    } else {
+                        tag.starts_with('~') || has(tag)
+                    }
+                })
+        })
+    }
+}
+
+/// Today in UTC as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    format_date((secs / 86_400) as i64)
+}
+
+/// One date per listing from `from` to `to` inclusive: every day, every 7th day or
+/// `from` followed by the first of each later month. An empty `to` means just `from`.
+pub fn dates(from: &str, to: &str, scale: Scale) -> Result<Vec<String>, String> {
+    let start = parse_date(from)?;
+    let end = if to.trim().is_empty() {
+        start
+    } else {
+        parse_date(to)?
+    };
+    if end < start {
+        return Err("The end date is before the start date.".to_owned());
+    }
+    let mut dates = Vec::new();
+    let mut day = start;
+    while day <= end {
+        dates.push(format_date(day));
+        day = match scale {
+            Scale::Day => day + 1,
+            Scale::Week => day + 7,
+            Scale::Month => {
+                let (year, month, _) = civil_from_days(day);
+                if month == 12 {
+                    days_from_civil(year + 1, 1, 1)
+                } else {
+                    days_from_civil(year, month + 1, 1)
+                }
+            }
+        };
+    }
+    Ok(dates)
+}
+
+/// Days since 1970-01-01 for a `YYYY-MM-DD` date.
+fn parse_date(text: &str) -> Result<i64, String> {
+    let invalid = || format!("'{}' is not a YYYY-MM-DD date.", text.trim());
+    let mut parts = text.trim().splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<i64>().ok());
+    let (Some(year), Some(month), Some(day)) = (next(), next(), next()) else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+    Ok(days_from_civil(year, month, day))
+}
+
+fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between proleptic Gregorian dates and days since the UNIX epoch, after
+// Howard Hinnant's `days_from_civil`/`civil_from_days`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_by_scale() {
+        assert_eq!(dates("2024-03-05", "", Scale::Day).unwrap(), ["2024-03-05"]);
+        assert_eq!(
+            dates("2024-03-01", "2024-03-15", Scale::Week).unwrap(),
+            ["2024-03-01", "2024-03-08", "2024-03-15"]
+        );
+        assert_eq!(
+            dates("2024-01-20", "2024-03-01", Scale::Month).unwrap(),
+            ["2024-01-20", "2024-02-01", "2024-03-01"]
+        );
+    }
+
+    #[test]
+    fn dates_roll_over_months_and_years() {
+        assert_eq!(
+            dates("2024-01-31", "2024-02-01", Scale::Day).unwrap(),
+            ["2024-01-31", "2024-02-01"]
+        );
+        assert_eq!(
+            dates("2023-12-31", "2024-01-02", Scale::Day).unwrap(),
+            ["2023-12-31", "2024-01-01", "2024-01-02"]
+        );
+        assert_eq!(
+            dates("2023-11-15", "2024-01-01", Scale::Month).unwrap(),
+            ["2023-11-15", "2023-12-01", "2024-01-01"]
+        );
+    }
+
+    #[test]
+    fn leap_years() {
+        assert_eq!(
+            dates("2024-02-28", "2024-03-01", Scale::Day).unwrap(),
+            ["2024-02-28", "2024-02-29", "2024-03-01"]
+        );
+        assert_eq!(
+            dates("2023-02-28", "2023-03-01", Scale::Day).unwrap(),
+            ["2023-02-28", "2023-03-01"]
+        );
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("2000-02-29").is_ok());
+        assert!(parse_date("1900-02-29").is_err());
+    }
+
+    #[test]
+    fn parse_date_round_trips() {
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert_eq!(
+            format_date(parse_date(" 2024-12-31 ").unwrap()),
+            "2024-12-31"
+        );
+        assert_eq!(format_date(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn parse_date_rejects_malformed() {
+        for text in [
+            "",
+            "2024",
+            "2024-13-01",
+            "2024-04-31",
+            "2024-00-10",
+            "2024-01-xx",
+        ] {
+            assert!(parse_date(text).is_err(), "{text:?} parsed");
+        }
+        assert!(dates("2024-02-02", "2024-02-01", Scale::Day).is_err());
+    }
+
+    #[test]
+    fn blacklist_rules() {
+        let blacklist = Blacklist::parse("gore\nfeet -socks\n~a ~b\nrating:e male\nscore:<0\n");
+        assert!(blacklist.hides(&["gore", "cat"], "s"));
+        assert!(blacklist.hides(&["feet"], "s"));
+        assert!(!blacklist.hides(&["feet", "socks"], "s"));
+        assert!(blacklist.hides(&["b"], "s"));
+        assert!(blacklist.hides(&["Male"], "e"));
+        assert!(!blacklist.hides(&["male"], "s"));
+        assert!(!blacklist.hides(&["cat"], "q"));
+        assert_eq!(blacklist.ignored, ["score:<0"]);
+    }
+}
@@ -0,0 +1,773 @@
+//! Parsing of e621 search queries for highlighting and for catching mistakes before a
+//! job is started: unknown metatags, the tag limit and sort orders that clash with the
+//! Sort selector. Aliased tags are also rewritten to their canonical names here.
+
+use crate::tagdb::TagDb;
+
+/// The site rejects searches with more tags than this.
+pub const TAG_LIMIT: usize = 40;
+
+/// Metatags the site understands, without the trailing colon.
+const METATAGS: &[&str] = &[
+    "approver",
+    "arttags",
+    "chartags",
+    "child",
+    "comment_count",
+    "commenter",
+    "copytags",
+    "date",
+    "delreason",
+    "deletedby",
+    "description",
+    "downvote",
+    "duration",
+    "fav",
+    "favcount",
+    "filesize",
+    "filetype",
+    "gentags",
+    "hasdescription",
+    "hassource",
+    "height",
+    "id",
+    "invtags",
+    "inpool",
+    "isparent",
+    "ischild",
+    "limit",
+    "locked",
+    "lortags",
+    "md5",
+    "metatags",
+    "mpixels",
+    "note",
+    "noter",
+    "notes",
+    "order",
+    "parent",
+    "pending_replacements",
+    "pool",
+    "randseed",
+    "rating",
+    "ratio",
+    "score",
+    "set",
+    "source",
+    "spectags",
+    "status",
+    "tagcount",
+    "type",
+    "upvote",
+    "user",
+    "voted",
+    "width",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Tag,
+    Metatag { known: bool },
+    GroupOpen,
+    GroupClose,
+}
+
+pub struct Token {
+    /// Byte range in the query, prefixes included.
+    pub start: usize,
+    pub end: usize,
+    pub kind: TokenKind,
+    /// `-tag`: must not be present.
+    pub negated: bool,
+    /// `~tag`: at least one of the `~` tags must be present.
+    pub or: bool,
+    /// The tag or metatag without its prefix, lower-cased.
+    pub name: String,
+}
+
+impl Token {
+    /// The part before the colon of a metatag.
+    pub fn metatag_name(&self) -> Option<&str> {
+        matches!(self.kind, TokenKind::Metatag { .. })
+            .then(|| self.name.split(':').next().unwrap_or_default())
+    }
+}
+
+pub fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in query.char_indices().chain([(query.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(from)) => {
+                tokens.push(token(&query[from..i], from));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+fn token(text: &str, start: usize) -> Token {
+    let end = start + text.len();
+    let group = match text {
+        "(" | "-(" | "~(" => Some(TokenKind::GroupOpen),
+        ")" => Some(TokenKind::GroupClose),
+        _ => None,
+    };
+    let negated = text.starts_with('-');
+    let or = text.starts_with('~');
+    let name = text.trim_start_matches(['-', '~']).to_lowercase();
+    let kind = group.unwrap_or_else(|| match name.split_once(':') {
+        // Tags like `16:9` contain colons too; only a word before the colon is a metatag.
+        Some((meta, _))
+            if !meta.is_empty() && meta.chars().all(|c| c.is_ascii_alphabetic() || c == '_') =>
+        {
+            TokenKind::Metatag {
+                known: METATAGS.contains(&meta),
+            }
+        }
+        _ => TokenKind::Tag,
+    });
+    Token {
+        start,
+        end,
+        kind,
+        negated,
+        or,
+        name,
+    }
+}
+
+/// Problems with `query` worth pointing out before it is sent. `order` is the Sort
+/// selector (which adds `order:<value>`), `implicit` the number of tags the job adds on
+/// its own, e.g. `fav:<user>`.
+pub fn check(query: &str, order: Option<&str>, implicit: usize, db: &TagDb) -> Vec<String> {
+    let tokens = tokenize(query);
+    let mut warnings = Vec::new();
+
+    let mut depth = 0i32;
+    for token in &tokens {
+        match token.kind {
+            TokenKind::GroupOpen => depth += 1,
+            TokenKind::GroupClose => depth -= 1,
+            _ => {}
+        }
+        if depth < 0 {
+            warnings.push("A ')' has no matching '('.".to_owned());
+            depth = 0;
+        }
+    }
+    if depth > 0 {
+        warnings.push("A '(' is never closed.".to_owned());
+    }
+
+    for token in &tokens {
+        if token.name.is_empty() && !matches!(token.kind, TokenKind::GroupOpen) {
+            warnings.push("A lone '-' or '~' has no tag after it.".to_owned());
+        }
+        match token.kind {
+            TokenKind::Metatag { known: false } if db.get(&token.name).is_none() => {
+                warnings.push(format!(
+                    "Unknown metatag '{}:'.",
+                    token.metatag_name().unwrap_or_default()
+                ));
+            }
+            TokenKind::Tag
+                if db.len() > 0
+                    && !token.name.is_empty()
+                    && !token.name.contains('*')
+                    && db.get(&token.name).is_none()
+                    && !db.aliases.contains_key(&token.name) =>
+            {
+                warnings.push(format!("Unknown tag '{}'.", token.name));
+            }
+            _ => {}
+        }
+    }
+
+    if tokens.iter().filter(|token| token.or).count() == 1 {
+        warnings.push("A single '~tag' does nothing; OR needs two or more.".to_owned());
+    }
+
+    let orders: Vec<&Token> = tokens
+        .iter()
+        .filter(|token| token.metatag_name() == Some("order"))
+        .collect();
+    if let (Some(order), false) = (order, orders.is_empty()) {
+        if orders
+            .iter()
+            .any(|token| token.name == format!("order:{order}"))
+        {
+            warnings.push(format!(
+                "'order:{order}' is already added by the Sort selector; remove one."
+            ));
+        } else {
+            warnings.push("The query sets an order, but so does the Sort selector.".to_owned());
+        }
+    }
+    if orders.len() > 1 {
+        warnings.push("Only one 'order:' is used; the query has several.".to_owned());
+    }
+
+    let count = tokens
+        .iter()
+        .filter(|token| matches!(token.kind, TokenKind::Tag | TokenKind::Metatag { .. }))
+        .count()
+        + implicit
+        + usize::from(order.is_some());
+    if count > TAG_LIMIT {
+        warnings.push(format!(
+            "{count} tags is over the site's limit of {TAG_LIMIT}."
+        ));
+    }
+    let mut unique = Vec::new();
+    for warning in warnings {
+        if !unique.contains(&warning) {
+            unique.push(warning);
+        }
+    }
+    unique
+}
+
+/// A query after alias resolution, with one line per change for the user.
+pub struct Resolved {
+    pub query: String,
+    pub changes: Vec<String>,
+}
+
+/// Replaces aliased tags with their canonical tag, keeping `-`/`~` prefixes. With
+/// `drop_implied`, plain (not negated, not OR) tags that another plain tag already
+/// implies are removed: they can't narrow the search and only use up the tag limit.
+pub fn resolve(query: &str, db: &TagDb, drop_implied: bool) -> Resolved {
+    let tokens = tokenize(query);
+    let mut resolved = String::new();
+    let mut changes = Vec::new();
+    let mut written = 0;
+    for token in &tokens {
+        if token.kind != TokenKind::Tag {
+            continue;
+        }
+        let Some(canonical) = db.aliases.get(&token.name) else {
+            continue;
+        };
+        let text = &query[token.start..token.end];
+        let prefix = &text[..text.len() - text.trim_start_matches(['-', '~']).len()];
+        resolved.push_str(&query[written..token.start]);
+        resolved.push_str(prefix);
+        resolved.push_str(canonical);
+        written = token.end;
+        changes.push(format!("{} \u{2192} {canonical}", token.name));
+    }
+    resolved.push_str(&query[written..]);
+
+    if drop_implied {
+        let tokens = tokenize(&resolved);
+        // Tags inside groups are left alone; their meaning depends on the group.
+        let mut depth = 0usize;
+        let mut plain: Vec<&Token> = Vec::new();
+        for token in &tokens {
+            match token.kind {
+                TokenKind::GroupOpen => depth += 1,
+                TokenKind::GroupClose => depth = depth.saturating_sub(1),
+                TokenKind::Tag if depth == 0 && !token.negated && !token.or => plain.push(token),
+                _ => {}
+            }
+        }
+        let mut dropped: Vec<&str> = Vec::new();
+        let mut kept = String::new();
+        let mut written = 0;
+        for token in &plain {
+            // Of two tags implying each other, only the first is dropped.
+            let implier = plain.iter().find(|other| {
+                other.name != token.name
+                    && !dropped.contains(&other.name.as_str())
+                    && db.implied_by(&other.name).contains(&token.name)
+            });
+            let Some(implier) = implier else {
+                continue;
+            };
+            dropped.push(&token.name);
+            changes.push(format!("- {} (implied by {})", token.name, implier.name));
+            kept.push_str(resolved[written..token.start].trim_end());
+            written = token.end;
+        }
+        kept.push_str(&resolved[written..]);
+        resolved = kept.trim().to_owned();
+    }
+    Resolved {
+        query: resolved,
+        changes,
+    }
+}
+
+/// Splits a query whose `~` group pushes it over the tag limit into several queries that
+/// each keep the other tags and take a share of the group. Together they match the same
+/// posts as the original. `implicit` is as for [`check`]. Queries that fit, or whose
+/// other tags alone leave no room, are returned unchanged.
+pub fn split_any_of(query: &str, implicit: usize) -> Vec<String> {
+    let tokens = tokenize(query);
+    let (any_of, rest): (Vec<&Token>, Vec<&Token>) = tokens
+        .iter()
+        .filter(|token| matches!(token.kind, TokenKind::Tag | TokenKind::Metatag { .. }))
+        .partition(|token| token.or && token.kind == TokenKind::Tag);
+    let grouped = tokens
+        .iter()
+        .any(|token| matches!(token.kind, TokenKind::GroupOpen | TokenKind::GroupClose));
+    let room = TAG_LIMIT.saturating_sub(rest.len() + implicit);
+    if grouped || any_of.len() + rest.len() + implicit <= TAG_LIMIT || room < 2 {
+        return vec![query.to_owned()];
+    }
+    let base: Vec<&str> = rest
+        .iter()
+        .map(|token| &query[token.start..token.end])
+        .collect();
+    let chunks = any_of.len().div_ceil(room);
+    let size = any_of.len().div_ceil(chunks);
+    any_of
+        .chunks(size)
+        .map(|chunk| {
+            let mut words = base.clone();
+            match chunk {
+                // A lone `~tag` is just a required tag.
+                [only] => words.push(&only.name),
+                _ => words.extend(chunk.iter().map(|token| &query[token.start..token.end])),
+            }
+            words.join(" ")
+        })
+        .collect()
+}
+
+/// Sort orders the site supports (`order:<value>`) with a label for the user. Without
+/// one the site lists newest first.
+pub const ORDERS: &[(&str, &str)] = &[
+    ("id_desc", "Newest"),
+    ("id", "Oldest"),
+    ("score", "Highest score"),
+    ("score_asc", "Lowest score"),
+    ("favcount", "Most favourites"),
+    ("favcount_asc", "Fewest favourites"),
+    ("comment_count", "Most comments"),
+    ("tagcount", "Most tags"),
+    ("mpixels", "Highest resolution"),
+    ("filesize", "Largest file"),
+    ("filesize_asc", "Smallest file"),
+    ("duration", "Longest"),
+    ("duration_asc", "Shortest"),
+    ("random", "Random"),
+];
+
+/// The label of an `order:` value, or the value itself when it isn't in [`ORDERS`].
+pub fn order_label(order: &str) -> &str {
+    ORDERS
+        .iter()
+        .find(|(value, _)| *value == order)
+        .map_or(order, |(_, label)| label)
+}
+
+/// File types offered by the query builder (`type:<value>`).
+pub const FILE_TYPES: &[&str] = &["jpg", "png", "gif", "webm", "mp4", "swf"];
+
+/// Ratings in `rating:` shorthand order: safe, questionable, explicit.
+const RATINGS: [char; 3] = ['s', 'q', 'e'];
+
+/// A query split into the parts the query builder edits. Anything it doesn't understand
+/// is kept in `other` so a round trip never loses part of a query.
+#[derive(Clone, Default)]
+pub struct QueryParts {
+    /// Space-separated tags that must be present.
+    pub include: String,
+    /// Space-separated tags that must be absent.
+    pub exclude: String,
+    /// Groups of space-separated tags of which at least one must be present.
+    pub any_of: Vec<String>,
+    /// Allowed ratings (safe, questionable, explicit); all or none means any.
+    pub ratings: [bool; 3],
+    pub score: (Option<i64>, Option<i64>),
+    pub favcount: (Option<i64>, Option<i64>),
+    /// `YYYY-MM-DD` bounds, empty when open.
+    pub date: (String, String),
+    pub file_type: Option<String>,
+    pub order: Option<String>,
+    pub other: String,
+}
+
+impl QueryParts {
+    pub fn parse(query: &str) -> Self {
+        let mut parts = Self::default();
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        let mut top_or = Vec::new();
+        let mut other = Vec::new();
+        let mut allowed: Option<[bool; 3]> = None;
+
+        let tokens = tokenize(query);
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            let text = &query[token.start..token.end];
+            i += 1;
+            if token.kind == TokenKind::GroupOpen {
+                // A plain group of `~tags` is an any-of group; anything else stays as typed.
+                let mut depth = 0usize;
+                let close = tokens[i..]
+                    .iter()
+                    .position(|inner| match inner.kind {
+                        TokenKind::GroupOpen => {
+                            depth += 1;
+                            false
+                        }
+                        TokenKind::GroupClose if depth == 0 => true,
+                        TokenKind::GroupClose => {
+                            depth -= 1;
+                            false
+                        }
+                        _ => false,
+                    })
+                    .map(|offset| i + offset);
+                let inner = close.map(|close| &tokens[i..close]);
+                match inner {
+                    Some(inner)
+                        if text == "("
+                            && !inner.is_empty()
+                            && inner.iter().all(|tag| tag.or && tag.kind == TokenKind::Tag) =>
+                    {
+                        let names: Vec<&str> = inner.iter().map(|tag| tag.name.as_str()).collect();
+                        parts.any_of.push(names.join(" "));
+                    }
+                    _ => {
+                        let end = close.map_or(query.len(), |close| tokens[close].end);
+                        other.push(query[token.start..end].to_owned());
+                    }
+                }
+                i = close.map_or(tokens.len(), |close| close + 1);
+                continue;
+            }
+            let value = |name: &str| token.name.strip_prefix(name).map(str::to_owned);
+            match token.kind {
+                TokenKind::Tag if token.or => top_or.push(token.name.clone()),
+                TokenKind::Tag if token.negated => exclude.push(token.name.clone()),
+                TokenKind::Tag => include.push(token.name.clone()),
+                _ if token.or => other.push(text.to_owned()),
+                _ => {
+                    if let Some(rating) = value("rating:").and_then(|value| value.chars().next()) {
+                        let Some(index) = RATINGS.iter().position(|r| *r == rating) else {
+                            other.push(text.to_owned());
+                            continue;
+                        };
+                        // `rating:x` allows only x; `-rating:x` takes x away from the rest.
+                        let mut ratings = if token.negated {
+                            allowed.unwrap_or([true; 3])
+                        } else {
+                            [false; 3]
+                        };
+                        ratings[index] = !token.negated;
+                        allowed = Some(ratings);
+                    } else if let (false, Some(range)) = (token.negated, value("score:")) {
+                        match parse_range(&range) {
+                            Some(range) => parts.score = range,
+                            None => other.push(text.to_owned()),
+                        }
+                    } else if let (false, Some(range)) = (token.negated, value("favcount:")) {
+                        match parse_range(&range) {
+                            Some(range) => parts.favcount = range,
+                            None => other.push(text.to_owned()),
+                        }
+                    } else if let (false, Some(range)) = (token.negated, value("date:")) {
+                        // Relative dates like `date:week` have no field of their own.
+                        match parse_date_range(&range) {
+                            Some(range) => parts.date = range,
+                            None => other.push(text.to_owned()),
+                        }
+                    } else if let (false, Some(file_type)) =
+                        (token.negated, value("type:").or_else(|| value("filetype:")))
+                    {
+                        parts.file_type = Some(file_type);
+                    } else if let (false, Some(order)) = (token.negated, value("order:")) {
+                        parts.order = Some(order);
+                    } else {
+                        other.push(text.to_owned());
+                    }
+                }
+            }
+        }
+        if !top_or.is_empty() {
+            parts.any_of.insert(0, top_or.join(" "));
+        }
+        parts.include = include.join(" ");
+        parts.exclude = exclude.join(" ");
+        parts.ratings = allowed.unwrap_or_default();
+        parts.other = other.join(" ");
+        parts
+    }
+
+    pub fn to_query(&self) -> String {
+        let mut out: Vec<String> = self.include.split_whitespace().map(str::to_owned).collect();
+        out.extend(self.exclude.split_whitespace().map(|tag| format!("-{tag}")));
+        let groups: Vec<Vec<&str>> = self
+            .any_of
+            .iter()
+            .map(|group| group.split_whitespace().collect::<Vec<_>>())
+            .filter(|group| !group.is_empty())
+            .collect();
+        for group in &groups {
+            let tags = group.iter().map(|tag| format!("~{tag}"));
+            if groups.len() == 1 {
+                out.extend(tags);
+            } else {
+                out.push(format!("( {} )", tags.collect::<Vec<_>>().join(" ")));
+            }
+        }
+        match self.ratings.iter().filter(|allowed| **allowed).count() {
+            1 => {
+                let index = self
+                    .ratings
+                    .iter()
+                    .position(|allowed| *allowed)
+                    .unwrap_or(0);
+                out.push(format!("rating:{}", RATINGS[index]));
+            }
+            2 => {
+                let index = self
+                    .ratings
+                    .iter()
+                    .position(|allowed| !*allowed)
+                    .unwrap_or(0);
+                out.push(format!("-rating:{}", RATINGS[index]));
+            }
+            _ => {}
+        }
+        out.extend(format_range("score", self.score));
+        out.extend(format_range("favcount", self.favcount));
+        match (self.date.0.trim(), self.date.1.trim()) {
+            ("", "") => {}
+            (from, "") => out.push(format!("date:>={from}")),
+            ("", to) => out.push(format!("date:<={to}")),
+            (from, to) => out.push(format!("date:{from}..{to}")),
+        }
+        if let Some(file_type) = &self.file_type {
+            out.push(format!("type:{file_type}"));
+        }
+        if let Some(order) = &self.order {
+            out.push(format!("order:{order}"));
+        }
+        if !self.other.trim().is_empty() {
+            out.push(self.other.trim().to_owned());
+        }
+        out.join(" ")
+    }
+}
+
+/// Parses `N`, `>=N`, `>N`, `<=N`, `<N`, `A..B`, `..B` and `A..` into inclusive bounds.
+fn parse_range(value: &str) -> Option<(Option<i64>, Option<i64>)> {
+    let number = |text: &str| -> Option<Option<i64>> {
+        if text.is_empty() {
+            Some(None)
+        } else {
+            text.parse().ok().map(Some)
+        }
+    };
+    if let Some((from, to)) = value.split_once("..") {
+        return Some((number(from)?, number(to)?));
+    }
+    if let Some(rest) = value.strip_prefix(">=") {
+        return Some((number(rest)?, None));
+    }
+    if let Some(rest) = value.strip_prefix("<=") {
+        return Some((None, number(rest)?));
+    }
+    if let Some(rest) = value.strip_prefix('>') {
+        return Some((number(rest)?.map(|n| n + 1), None));
+    }
+    if let Some(rest) = value.strip_prefix('<') {
+        return Some((None, number(rest)?.map(|n| n - 1)));
+    }
+    let exact = number(value)?;
+    Some((exact, exact))
+}
+
+fn format_range(name: &str, (min, max): (Option<i64>, Option<i64>)) -> Option<String> {
+    match (min, max) {
+        (None, None) => None,
+        (Some(min), None) => Some(format!("{name}:>={min}")),
+        (None, Some(max)) => Some(format!("{name}:<={max}")),
+        (Some(min), Some(max)) if min == max => Some(format!("{name}:{min}")),
+        (Some(min), Some(max)) => Some(format!("{name}:{min}..{max}")),
+    }
+}
+
+/// Parses `D`, `>=D`, `>D`, `<=D`, `<D` and `A..B` (either side may be empty) for
+/// `YYYY-MM-DD` dates.
+fn parse_date_range(value: &str) -> Option<(String, String)> {
+    let date = |text: &str| {
+        let is_date = text.len() == 10
+            && text.char_indices().all(|(i, c)| match i {
+                4 | 7 => c == '-',
+                _ => c.is_ascii_digit(),
+            });
+        (text.is_empty() || is_date).then(|| text.to_owned())
+    };
+    let range = if let Some((from, to)) = value.split_once("..") {
+        (date(from)?, date(to)?)
+    } else if let Some(from) = value.strip_prefix(">=").or_else(|| value.strip_prefix('>')) {
+        (date(from)?, String::new())
+    } else if let Some(to) = value.strip_prefix("<=").or_else(|| value.strip_prefix('<')) {
+        (String::new(), date(to)?)
+    } else {
+        (date(value)?, date(value)?)
+    };
+    (!range.0.is_empty() || !range.1.is_empty()).then_some(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_kinds_and_ranges() {
+        let query = "  Cat -rating:e ~dog 16:9 ( ~a ~b ) foo:bar";
+        let tokens = tokenize(query);
+        let texts: Vec<&str> = tokens
+            .iter()
+            .map(|token| &query[token.start..token.end])
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "Cat",
+                "-rating:e",
+                "~dog",
+                "16:9",
+                "(",
+                "~a",
+                "~b",
+                ")",
+                "foo:bar"
+            ]
+        );
+        assert_eq!(tokens[0].name, "cat");
+        assert!(tokens[1].negated);
+        assert!(tokens[1].kind == TokenKind::Metatag { known: true });
+        assert_eq!(tokens[1].metatag_name(), Some("rating"));
+        assert!(tokens[2].or && tokens[2].kind == TokenKind::Tag);
+        assert!(tokens[3].kind == TokenKind::Tag);
+        assert!(tokens[4].kind == TokenKind::GroupOpen);
+        assert!(tokens[7].kind == TokenKind::GroupClose);
+        assert!(tokens[8].kind == TokenKind::Metatag { known: false });
+    }
+
+    #[test]
+    fn check_warnings() {
+        let db = TagDb::default();
+        assert!(check("cat dog", None, 0, &db).is_empty());
+        assert_eq!(check("( cat", None, 0, &db), ["A '(' is never closed."]);
+        assert_eq!(check("cat )", None, 0, &db), ["A ')' has no matching '('."]);
+        assert_eq!(
+            check("cat ~dog", None, 0, &db),
+            ["A single '~tag' does nothing; OR needs two or more."]
+        );
+        assert_eq!(check("foo:bar", None, 0, &db), ["Unknown metatag 'foo:'."]);
+        assert_eq!(
+            check("cat order:score", Some("score"), 0, &db),
+            ["'order:score' is already added by the Sort selector; remove one."]
+        );
+        let tags: Vec<String> = (0..TAG_LIMIT).map(|i| format!("tag{i}")).collect();
+        assert!(check(&tags.join(" "), None, 0, &db).is_empty());
+        assert_eq!(
+            check(&tags.join(" "), None, 1, &db),
+            [format!(
+                "{} tags is over the site's limit of {TAG_LIMIT}.",
+                TAG_LIMIT + 1
+            )]
+        );
+    }
+
+    #[test]
+    fn split_any_of_shares_the_group() {
+        assert_eq!(split_any_of("cat ~a ~b", 0), ["cat ~a ~b"]);
+        let group: Vec<String> = (0..TAG_LIMIT).map(|i| format!("~t{i}")).collect();
+        let query = format!("cat -dog {}", group.join(" "));
+        let split = split_any_of(&query, 1);
+        assert_eq!(split.len(), 2);
+        let mut rejoined = Vec::new();
+        for part in &split {
+            assert!(part.starts_with("cat -dog ~t"));
+            assert!(check(part, None, 1, &TagDb::default()).is_empty());
+            rejoined.extend(part.split(' ').skip(2).map(str::to_owned));
+        }
+        assert_eq!(rejoined, group);
+        // Groups in parentheses are left alone.
+        let grouped = format!("cat ( {} )", group.join(" "));
+        assert_eq!(split_any_of(&grouped, 0), [grouped]);
+    }
+
+    #[test]
+    fn split_any_of_lone_tag_becomes_required() {
+        let rest: Vec<String> = (0..TAG_LIMIT - 2).map(|i| format!("r{i}")).collect();
+        let query = format!("{} ~a ~b ~c", rest.join(" "));
+        let split = split_any_of(&query, 0);
+        assert_eq!(split.len(), 2);
+        assert!(split[0].ends_with(" ~a ~b"));
+        assert!(split[1].ends_with(" c"));
+    }
+
+    #[test]
+    fn query_parts_round_trip() {
+        let query = "cat dog -gore ~red ~blue -rating:e score:>=10 favcount:5..20 \
+                     date:2024-01-01..2024-02-01 type:png order:score ( -x y )";
+        let parts = QueryParts::parse(query);
+        assert_eq!(parts.include, "cat dog");
+        assert_eq!(parts.exclude, "gore");
+        assert_eq!(parts.any_of, ["red blue"]);
+        assert_eq!(parts.ratings, [true, true, false]);
+        assert_eq!(parts.score, (Some(10), None));
+        assert_eq!(parts.favcount, (Some(5), Some(20)));
+        assert_eq!(
+            parts.date,
+            ("2024-01-01".to_owned(), "2024-02-01".to_owned())
+        );
+        assert_eq!(parts.file_type.as_deref(), Some("png"));
+        assert_eq!(parts.order.as_deref(), Some("score"));
+        assert_eq!(parts.other, "( -x y )");
+        let again = QueryParts::parse(&parts.to_query());
+        assert_eq!(again.to_query(), parts.to_query());
+        assert_eq!(
+            parts.to_query(),
+            "cat dog -gore ~red ~blue -rating:e score:>=10 favcount:5..20 \
+             date:2024-01-01..2024-02-01 type:png order:score ( -x y )"
+        );
+    }
+
+    #[test]
+    fn query_parts_nested_groups_stay_whole() {
+        let parts = QueryParts::parse("-( ( ~a ~b ) c ) d");
+        assert_eq!(parts.other, "-( ( ~a ~b ) c )");
+        assert_eq!(parts.include, "d");
+        assert_eq!(parts.to_query(), "d -( ( ~a ~b ) c )");
+    }
+
+    #[test]
+    fn query_parts_relative_dates_stay_in_other() {
+        for query in [
+            "date:today",
+            "date:week",
+            "date:3_days_ago",
+            "date:>=yesterday",
+        ] {
+            let parts = QueryParts::parse(query);
+            assert_eq!(parts.date, (String::new(), String::new()));
+            assert_eq!(parts.to_query(), query);
+        }
+        let parts = QueryParts::parse("date:<2024-05-01");
+        assert_eq!(parts.date, (String::new(), "2024-05-01".to_owned()));
+    }
+
+    #[test]
+    fn query_parts_any_of_groups() {
+        let parts = QueryParts::parse("( ~a ~b ) ( ~c ~d ) score:>3");
+        assert_eq!(parts.any_of, ["a b", "c d"]);
+        assert_eq!(parts.score, (Some(4), None));
+        assert_eq!(parts.to_query(), "( ~a ~b ) ( ~c ~d ) score:>=4");
+    }
+}
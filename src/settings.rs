@@ -0,0 +1,82 @@
+//! GUI-only settings that have no place in e-cli's shared `config.toml`. They live next
+//! to it in `gui.toml`, and presets are matched to e-cli's presets by name.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use e_cli::config as econfig;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuiSettings {
+    pub set: SetSettings,
+    pub orders: OrderSettings,
+    /// Every pool on the Pool tab when it holds more than one. e-cli's config only keeps
+    /// the first, so this list is used while that first id still matches.
+    pub pools: Vec<u64>,
+    /// Artist tags on the watchlist.
+    pub watchlist: Vec<String>,
+    pub presets: BTreeMap<String, GuiPreset>,
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SetSettings {
+    pub set: Option<String>,
+    pub ordered: Option<bool>,
+}
+
+/// Sort orders of the Favourites and Tags tabs. e-cli's config only knows Random, which
+/// stays there; any other order is kept here.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrderSettings {
+    pub favourites: Option<String>,
+    pub tags: Option<String>,
+}
+
+/// The parts of a preset that e-cli's `PresetConfig` can't hold.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuiPreset {
+    pub set: Option<String>,
+    pub set_ordered: Option<bool>,
+    /// Pool presets with several pools; e-cli's preset keeps the first one.
+    pub pools: Vec<u64>,
+    /// `order:` value for Favourites and Tags presets; missing means the site's default.
+    pub order: Option<String>,
+    /// Job limits; missing means no limit.
+    pub max_mb: Option<u64>,
+    pub max_files: Option<u64>,
+    pub max_minutes: Option<u64>,
+}
+
+pub fn path() -> Result<PathBuf, String> {
+    econfig::path()
+        .map(|path| path.with_file_name("gui.toml"))
+        .map_err(|error| error.to_string())
+}
+
+/// Loads `gui.toml`, treating a missing file as empty settings.
+pub fn load() -> Result<GuiSettings, String> {
+    let path = path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(text) => {
+            toml::from_str(&text).map_err(|error| format!("Invalid {}: {error}", path.display()))
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(GuiSettings::default()),
+        Err(error) => Err(format!("Could not read {}: {error}", path.display())),
+    }
+}
+
+pub fn save(settings: &GuiSettings) -> Result<(), String> {
+    let path = path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|error| format!("Could not create {}: {error}", parent.display()))?;
+    }
+    let text = toml::to_string_pretty(settings).map_err(|error| error.to_string())?;
+    std::fs::write(&path, text)
+        .map_err(|error| format!("Could not write {}: {error}", path.display()))
+}
@@ -0,0 +1,319 @@
+//! Local tag database for autocomplete and alias/implication resolution. It is imported
+//! from e621's public database dumps (`tags-*.csv`, `tag_aliases-*.csv`,
+//! `tag_implications-*.csv`, optionally gzipped) or refreshed from the API, and kept next
+//! to `gui.toml` as `tags.tsv`, `tag_aliases.tsv` and `tag_implications.tsv`.
+
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::format::csv_fields;
+use crate::settings;
+
+const TAGS_FILE: &str = "tags.tsv";
+const ALIASES_FILE: &str = "tag_aliases.tsv";
+const IMPLICATIONS_FILE: &str = "tag_implications.tsv";
+
+#[derive(Clone, Deserialize)]
+pub struct TagEntry {
+    pub name: String,
+    /// e621's category number: 0 general, 1 artist, 3 copyright, 4 character, 5 species,
+    /// 6 invalid, 7 meta, 8 lore.
+    #[serde(default)]
+    pub category: u8,
+    #[serde(default)]
+    pub post_count: u64,
+}
+
+#[derive(Clone)]
+pub struct Suggestion {
+    pub tag: TagEntry,
+    /// The alias that was typed, when the suggestion is its canonical tag.
+    pub alias: Option<String>,
+}
+
+#[derive(Default)]
+pub struct TagDb {
+    /// Sorted by name.
+    tags: Vec<TagEntry>,
+    /// Active aliases, antecedent to consequent.
+    pub aliases: BTreeMap<String, String>,
+    /// Active implications, antecedent to the tags it implies.
+    pub implications: BTreeMap<String, Vec<String>>,
+}
+
+fn dir() -> Result<PathBuf, String> {
+    let path = settings::path()?;
+    Ok(path.parent().map(Path::to_path_buf).unwrap_or_default())
+}
+
+impl TagDb {
+    /// Loads the stored database, treating missing files as empty.
+    pub fn load() -> Result<Self, String> {
+        let dir = dir()?;
+        let mut db = Self::default();
+        for line in read_lines(&dir.join(TAGS_FILE))? {
+            let mut fields = line.split('\t');
+            let (Some(name), Some(category), Some(count)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            db.tags.push(TagEntry {
+                name: name.to_owned(),
+                category: category.parse().unwrap_or(0),
+                post_count: count.parse().unwrap_or(0),
+            });
+        }
+        db.tags.sort_by(|a, b| a.name.cmp(&b.name));
+        for line in read_lines(&dir.join(ALIASES_FILE))? {
+            if let Some((antecedent, consequent)) = line.split_once('\t') {
+                db.aliases
+                    .insert(antecedent.to_owned(), consequent.to_owned());
+            }
+        }
+        for line in read_lines(&dir.join(IMPLICATIONS_FILE))? {
+            if let Some((antecedent, consequent)) = line.split_once('\t') {
+                db.add_implication(antecedent, consequent);
+            }
+        }
+        Ok(db)
+    }
+
+    fn add_implication(&mut self, antecedent: &str, consequent: &str) {
+        let implied = self.implications.entry(antecedent.to_owned()).or_default();
+        if !implied.iter().any(|tag| tag == consequent) {
+            implied.push(consequent.to_owned());
+        }
+    }
+
+    /// Adds aliases and implications fetched from the site and saves both tables.
+    pub fn merge_relations(
+        &mut self,
+        aliases: Vec<(String, String)>,
+        implications: Vec<(String, String)>,
+    ) -> Result<(), String> {
+        self.aliases.extend(aliases);
+        for (antecedent, consequent) in &implications {
+            self.add_implication(antecedent, consequent);
+        }
+        let dir = dir()?;
+        std::fs::create_dir_all(&dir)
+            .map_err(|error| format!("Could not create {}: {error}", dir.display()))?;
+        let aliases: String = self
+            .aliases
+            .iter()
+            .map(|(antecedent, consequent)| format!("{antecedent}\t{consequent}\n"))
+            .collect();
+        write(&dir.join(ALIASES_FILE), &aliases)?;
+        let implications: String = self
+            .implications
+            .iter()
+            .flat_map(|(antecedent, implied)| {
+                implied
+                    .iter()
+                    .map(move |consequent| format!("{antecedent}\t{consequent}\n"))
+            })
+            .collect();
+        write(&dir.join(IMPLICATIONS_FILE), &implications)
+    }
+
+    /// Every tag `tag` implies, directly or through other implications.
+    pub fn implied_by(&self, tag: &str) -> Vec<String> {
+        let mut implied: Vec<String> = Vec::new();
+        let mut pending = vec![tag.to_owned()];
+        while let Some(next) = pending.pop() {
+            for consequent in self.implications.get(&next).into_iter().flatten() {
+                if consequent != tag && !implied.contains(consequent) {
+                    implied.push(consequent.clone());
+                    pending.push(consequent.clone());
+                }
+            }
+        }
+        implied
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TagEntry> {
+        self.tags
+            .binary_search_by(|tag| tag.name.as_str().cmp(name))
+            .ok()
+            .map(|i| &self.tags[i])
+    }
+
+    /// Up to `limit` tags starting with `prefix`, most used first. Aliases starting with
+    /// `prefix` suggest their canonical tag, unless it is already listed. Only the chosen
+    /// tags are cloned.
+    pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<Suggestion> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        let start = self.tags.partition_point(|tag| tag.name.as_str() < prefix);
+        let tags = self.tags[start..]
+            .iter()
+            .take_while(|tag| tag.name.starts_with(prefix))
+            .map(|tag| (tag, None));
+        let aliases = self
+            .aliases
+            .range(prefix.to_owned()..)
+            .take_while(|(antecedent, _)| antecedent.starts_with(prefix))
+            .filter_map(|(antecedent, consequent)| Some((self.get(consequent)?, Some(antecedent))));
+        // The heap's top is the weakest candidate kept so far.
+        let mut best = BinaryHeap::with_capacity(limit + 1);
+        let mut listed = HashSet::new();
+        for (order, (tag, alias)) in tags.chain(aliases).enumerate() {
+            // A tag's first candidate is its best one: tags come before their aliases.
+            if !listed.insert(tag.name.as_str()) {
+                continue;
+            }
+            best.push(Candidate { order, tag, alias });
+            if best.len() > limit {
+                best.pop();
+            }
+        }
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|candidate| Suggestion {
+                tag: candidate.tag.clone(),
+                alias: candidate.alias.cloned(),
+            })
+            .collect()
+    }
+}
+
+/// A suggestion before it is cloned. Orders best first: more posts, then tags before
+/// aliases and in name order.
+struct Candidate<'a> {
+    order: usize,
+    tag: &'a TagEntry,
+    alias: Option<&'a String>,
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other
+            .tag
+            .post_count
+            .cmp(&self.tag.post_count)
+            .then(self.order.cmp(&other.order))
+    }
+}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+/// Imports e621 dump files into the stored database, replacing what was there for each
+/// kind of dump given. Returns a summary for the user.
+pub fn import_dumps(paths: &[PathBuf]) -> Result<String, String> {
+    let dir = dir()?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|error| format!("Could not create {}: {error}", dir.display()))?;
+    let mut summary = Vec::new();
+    for path in paths {
+        if !path.is_file() {
+            return Err(format!("No file {} found.", path.display()));
+        }
+        let lines = read_lines(path)?;
+        let mut lines = lines.into_iter();
+        let header = csv_fields(&lines.next().unwrap_or_default());
+        let column = |name: &str| header.iter().position(|field| field == name);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        if let (Some(name), Some(category), Some(count)) =
+            (column("name"), column("category"), column("post_count"))
+        {
+            let mut out = String::new();
+            let mut imported = 0;
+            for line in lines {
+                let fields = csv_fields(&line);
+                let (Some(tag), Some(category), Some(count)) =
+                    (fields.get(name), fields.get(category), fields.get(count))
+                else {
+                    continue;
+                };
+                // Empty tags only clutter the suggestions.
+                if count == "0" {
+                    continue;
+                }
+                out.push_str(&format!("{tag}\t{category}\t{count}\n"));
+                imported += 1;
+            }
+            write(&dir.join(TAGS_FILE), &out)?;
+            summary.push(format!("{imported} tags"));
+        } else if let (Some(antecedent), Some(consequent)) =
+            (column("antecedent_name"), column("consequent_name"))
+        {
+            let implications = file_name.contains("implication");
+            let status = column("status");
+            let mut out = String::new();
+            let mut imported = 0;
+            for line in lines {
+                let fields = csv_fields(&line);
+                let active = status
+                    .and_then(|status| fields.get(status))
+                    .is_none_or(|status| status == "active");
+                if let (true, Some(antecedent), Some(consequent)) =
+                    (active, fields.get(antecedent), fields.get(consequent))
+                {
+                    out.push_str(&format!("{antecedent}\t{consequent}\n"));
+                    imported += 1;
+                }
+            }
+            if implications {
+                write(&dir.join(IMPLICATIONS_FILE), &out)?;
+                summary.push(format!("{imported} implications"));
+            } else {
+                write(&dir.join(ALIASES_FILE), &out)?;
+                summary.push(format!("{imported} aliases"));
+            }
+        } else {
+            return Err(format!(
+                "{} is not an e621 tag, alias or implication dump.",
+                path.display()
+            ));
+        }
+    }
+    Ok(format!("Imported {}.", summary.join(", ")))
+}
+
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    std::fs::write(path, text)
+        .map_err(|error| format!("Could not write {}: {error}", path.display()))
+}
+
+/// Lines of a text file, transparently gunzipping `.gz` files. A missing file has none.
+fn read_lines(path: &Path) -> Result<Vec<String>, String> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(format!("Could not read {}: {error}", path.display())),
+    };
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    BufReader::new(reader)
+        .lines()
+        .collect::<Result<_, _>>()
+        .map_err(|error| format!("Could not read {}: {error}", path.display()))
+}
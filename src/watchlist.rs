@@ -0,0 +1,114 @@
+//! The artist watchlist: artist tags whose posts are downloaded into
+//! `artists/<name>/`, with their artist entries and local vs available counts.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use e_cli::Login;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{self, ArtistEntry, Throttle};
+
+const FETCHED_FILE: &str = ".e-cli-artists.json";
+
+/// What is known about one watched artist after a refresh.
+pub struct ArtistStatus {
+    pub name: String,
+    /// `None` when the site has no artist entry for the tag.
+    pub entry: Option<ArtistEntry>,
+    /// Files in the artist's folder.
+    pub local: usize,
+    /// Posts tagged with the artist, when the lookup worked.
+    pub available: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// The artist's folder relative to the download directory.
+pub fn folder(name: &str) -> PathBuf {
+    Path::new("artists").join(crate::format::folder_name(name, "_"))
+}
+
+/// Which posts of each artist were fetched, kept in the download directory as
+/// `.e-cli-artists.json`. A range is only stored once every post in it was downloaded or
+/// skipped, so "new posts" never passes over one that failed.
+#[derive(Default, Serialize, Deserialize)]
+pub struct FetchedIds {
+    pub artists: BTreeMap<String, FetchedRange>,
+}
+
+/// Every post of an artist from `oldest` (or their first post when `None`) up to
+/// `newest` has been fetched.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct FetchedRange {
+    pub newest: u64,
+    /// Older posts below this id were cut off by the page limit and are still due.
+    pub oldest: Option<u64>,
+}
+
+impl FetchedIds {
+    pub fn path(dl_dir: &Path) -> PathBuf {
+        dl_dir.join(FETCHED_FILE)
+    }
+
+    /// Loads the ids, treating a missing file as empty.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|error| format!("Invalid artist ids {}: {error}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(format!("Could not read {}: {error}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, text)
+            .map_err(|error| format!("Could not write {}: {error}", path.display()))
+    }
+}
+
+/// Files (not hidden, not in subfolders) in the artist's folder under `dl_dir`.
+pub fn local_count(dl_dir: &Path, name: &str) -> usize {
+    let Ok(entries) = std::fs::read_dir(dl_dir.join(folder(name))) else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .count()
+}
+
+/// Looks up every artist's entry and post count, spacing the requests out. Failures are
+/// kept per artist so one bad tag doesn't hide the rest.
+pub fn fetch_status(
+    nsfw: bool,
+    login: &Login,
+    dl_dir: &Path,
+    names: &[String],
+) -> Vec<ArtistStatus> {
+    let mut throttle = Throttle::default();
+    names
+        .iter()
+        .map(|name| {
+            let mut status = ArtistStatus {
+                name: name.clone(),
+                entry: None,
+                local: local_count(dl_dir, name),
+                available: None,
+                error: None,
+            };
+            throttle.wait();
+            match api::artist_entry(nsfw, login, name) {
+                Ok(entry) => status.entry = entry,
+                Err(error) => status.error = Some(error),
+            }
+            throttle.wait();
+            match api::tag_post_count(nsfw, login, name) {
+                Ok(count) => status.available = Some(count),
+                Err(error) => status.error = Some(error),
+            }
+            status
+        })
+        .collect()
+}